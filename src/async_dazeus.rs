@@ -0,0 +1,379 @@
+use super::dazeus::{check_response, subscribe_request};
+use super::error::Error;
+use super::event::{Event, EventType};
+use super::handler::{closed_error, encode, Buffer, Message};
+use super::request::Request;
+use super::response::Response;
+use futures_core::Stream;
+use log::warn;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+
+/// Where the response to a request should be delivered.
+type Reply = oneshot::Sender<Result<Response, Error>>;
+
+/// The replies of requests that were sent, in the order the requests were sent in.
+///
+/// Becomes `None` once the connection to DaZeus is lost.
+type Pending = Arc<Mutex<Option<VecDeque<Reply>>>>;
+
+/// An asynchronous connection to DaZeus, for use with the Tokio runtime.
+///
+/// Requests are sent using `AsyncDaZeus::send()` or one of the convenience methods, and their
+/// responses can be awaited. Events for which a subscription was made are received through the
+/// `EventStream` that is created together with the connection. This requires the `async`
+/// feature.
+///
+/// An `AsyncDaZeus` can be cloned to send requests from several tasks at once. The connection is
+/// closed once every clone has been dropped.
+///
+/// # Example
+/// ```no_run
+/// # use dazeus::*;
+/// # async fn run() -> Result<(), Error> {
+/// let (dazeus, mut events) = AsyncDaZeus::connect("unix:/tmp/dazeus.sock").await?;
+/// dazeus.subscribe(EventType::PrivMsg).await?;
+/// while let Some(evt) = events.next_event().await {
+///     dazeus.message(&evt[0], &evt[2], &evt[3]).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncDaZeus {
+    requests: mpsc::UnboundedSender<(Vec<u8>, Reply)>,
+}
+
+impl AsyncDaZeus {
+    /// Start communicating with DaZeus over the given stream.
+    ///
+    /// Reading from and writing to the stream is done by tasks spawned on the current Tokio
+    /// runtime, so this panics when called outside of a runtime.
+    pub fn new<S>(stream: S) -> (AsyncDaZeus, EventStream)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = io::split(stream);
+        let pending: Pending = Arc::new(Mutex::new(Some(VecDeque::new())));
+        let (requests, outgoing) = mpsc::unbounded_channel();
+        let (incoming, events) = mpsc::unbounded_channel();
+        tokio::spawn(write_requests(writer, outgoing, pending.clone()));
+        tokio::spawn(read_messages(reader, incoming, pending));
+        (AsyncDaZeus { requests }, EventStream { events })
+    }
+
+    /// Connect to DaZeus using a string in the format type:connection_str, as used by
+    /// `Connection::from_str()`.
+    pub async fn connect(connection_str: &str) -> Result<(AsyncDaZeus, EventStream), Error> {
+        let splits = connection_str.splitn(2, ':').collect::<Vec<_>>();
+        if splits.len() == 2 && splits[0] == "unix" {
+            Ok(AsyncDaZeus::new(UnixStream::connect(splits[1]).await?))
+        } else if splits.len() == 2 && splits[0] == "tcp" {
+            Ok(AsyncDaZeus::new(TcpStream::connect(splits[1]).await?))
+        } else {
+            Err(Error::IoError(IoError::new(
+                ErrorKind::InvalidInput,
+                "Unknown connection type",
+            )))
+        }
+    }
+
+    /// Send a request to DaZeus, returning a future for its response.
+    ///
+    /// Note that the response is returned as is, even when it indicates that the core could not
+    /// fulfill the request. The request is sent even if the returned future is never awaited, so
+    /// requests are always sent in the order this method was called in.
+    pub fn send(&self, request: Request) -> impl Future<Output = Result<Response, Error>> {
+        let (reply, response) = oneshot::channel();
+        let sent = self.requests.send((encode(&request), reply));
+        async move {
+            sent.map_err(|_| Error::IoError(closed_error()))?;
+            response
+                .await
+                .unwrap_or_else(|_| Err(Error::IoError(closed_error())))
+        }
+    }
+
+    /// Send a request and turn a response indicating failure into an `Error::CoreRejected`.
+    async fn send_checked(&self, request: Request) -> Result<Response, Error> {
+        let description = format!("{:?}", request);
+        check_response(description, self.send(request).await?)
+    }
+
+    /// Send a handshake to the DaZeus core.
+    pub async fn handshake(
+        &self,
+        name: &str,
+        version: &str,
+        config: Option<&str>,
+    ) -> Result<Response, Error> {
+        let config = config.map(|config_name| config_name.to_string());
+        let request = Request::Handshake(name.to_string(), version.to_string(), config);
+        self.send_checked(request).await
+    }
+
+    /// Subscribe to an event type, after which such events are received by the `EventStream`.
+    pub async fn subscribe(&self, event: EventType) -> Result<Response, Error> {
+        self.send_checked(subscribe_request(event)).await
+    }
+
+    /// Subscribe to a command, optionally only on a single network.
+    pub async fn subscribe_command(
+        &self,
+        command: &str,
+        network: Option<&str>,
+    ) -> Result<Response, Error> {
+        let network = network.map(|network| network.to_string());
+        let request = Request::SubscribeCommand(command.to_string(), network);
+        self.send_checked(request).await
+    }
+
+    /// Stop receiving events of the given type.
+    pub async fn unsubscribe(&self, event: EventType) -> Result<Response, Error> {
+        self.send_checked(Request::Unsubscribe(event)).await
+    }
+
+    /// Send a message to a channel or user on a network.
+    pub async fn message(
+        &self,
+        network: &str,
+        channel: &str,
+        message: &str,
+    ) -> Result<Response, Error> {
+        let request = Request::Message(
+            network.to_string(),
+            channel.to_string(),
+            message.to_string(),
+        );
+        self.send_checked(request).await
+    }
+}
+
+/// The events received from DaZeus by an `AsyncDaZeus`.
+///
+/// The stream ends when the connection to DaZeus is lost.
+pub struct EventStream {
+    events: mpsc::UnboundedReceiver<Event>,
+}
+
+impl EventStream {
+    /// Wait for the next event, returning `None` when the connection to DaZeus is lost.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Event>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+/// Write requests to DaZeus, until every `AsyncDaZeus` has been dropped.
+async fn write_requests<W>(
+    mut writer: W,
+    mut outgoing: mpsc::UnboundedReceiver<(Vec<u8>, Reply)>,
+    pending: Pending,
+) where
+    W: AsyncWrite + Unpin,
+{
+    while let Some((message, reply)) = outgoing.recv().await {
+        // wait for the response before writing, so it can't arrive before it is expected
+        match *pending.lock().unwrap() {
+            Some(ref mut replies) => replies.push_back(reply),
+            None => continue,
+        }
+
+        if let Err(e) = writer.write_all(&message).await {
+            warn!("Could not send request to DaZeus: {}", e);
+            pending.lock().unwrap().take();
+            return;
+        }
+    }
+
+    let _ = writer.shutdown().await;
+}
+
+/// Read messages from DaZeus, passing on events and responses until the connection is lost.
+async fn read_messages<R>(mut reader: R, events: mpsc::UnboundedSender<Event>, pending: Pending)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buffer = Buffer::new();
+    let mut data = [0; 1024];
+    loop {
+        while let Some(message) = buffer.next_message() {
+            match message {
+                Ok(Message::Event(event)) => {
+                    // nobody may be listening for events, which is fine
+                    let _ = events.send(event);
+                }
+                Ok(Message::Response(response)) => respond(&pending, Ok(response)),
+                Err(e) => respond(&pending, Err(e)),
+            }
+        }
+
+        match reader.read(&mut data).await {
+            Ok(0) => break,
+            Ok(bytes) => buffer.extend(&data[..bytes]),
+            Err(e) => {
+                warn!("Could not receive data from DaZeus: {}", e);
+                break;
+            }
+        }
+    }
+
+    // requests still waiting for a response fail once their replies are dropped
+    pending.lock().unwrap().take();
+}
+
+/// Deliver a response (or the error received instead) to the oldest request.
+fn respond(pending: &Pending, result: Result<Response, Error>) {
+    let reply = pending
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|replies| replies.pop_front());
+    match (reply, result) {
+        (Some(reply), result) => {
+            // the request may no longer be waiting for its response
+            let _ = reply.send(result);
+        }
+        (None, Ok(_)) => warn!("Received a response from DaZeus without sending a request"),
+        (None, Err(e)) => warn!("Received an invalid message from DaZeus: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value as JsonValue};
+    use std::future::poll_fn;
+    use tokio::io::DuplexStream;
+
+    fn run<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Read the next request sent to the core.
+    async fn read_request(core: &mut DuplexStream) -> Request {
+        let mut len = 0;
+        loop {
+            match core.read_u8().await.unwrap() {
+                digit @ b'0'..=b'9' => len = len * 10 + (digit - b'0') as usize,
+                first => {
+                    let mut data = vec![first; len];
+                    core.read_exact(&mut data[1..]).await.unwrap();
+                    let json = serde_json::from_slice(&data).unwrap();
+                    return Request::from_json(&json).unwrap();
+                }
+            }
+        }
+    }
+
+    async fn write_json(core: &mut DuplexStream, json: JsonValue) {
+        let encoded = json.to_string();
+        let message = format!("{}{}", encoded.len(), encoded);
+        core.write_all(message.as_bytes()).await.unwrap();
+    }
+
+    fn nick(nick: &str) -> JsonValue {
+        json!({"did": "nick", "success": true, "nick": nick})
+    }
+
+    #[test]
+    fn responses_are_delivered_in_order() {
+        run(async {
+            let (client, mut core) = io::duplex(1024);
+            let (dazeus, _events) = AsyncDaZeus::new(client);
+            let first = dazeus.send(Request::Nick("first".to_string()));
+            let second = dazeus.send(Request::Nick("second".to_string()));
+            let core = tokio::spawn(async move {
+                for network in &["first", "second"] {
+                    let request = read_request(&mut core).await;
+                    assert_eq!(request, Request::Nick(network.to_string()));
+                    write_json(&mut core, nick(network)).await;
+                }
+                core
+            });
+
+            assert_eq!(second.await.unwrap().get_str("nick"), Some("second"));
+            assert_eq!(first.await.unwrap().get_str("nick"), Some("first"));
+            core.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn events_are_streamed() {
+        run(async {
+            let (client, mut core) = io::duplex(1024);
+            let (dazeus, mut events) = AsyncDaZeus::new(client);
+            let subscribe = dazeus.subscribe(EventType::PrivMsg);
+            let core = tokio::spawn(async move {
+                let request = read_request(&mut core).await;
+                assert_eq!(request, Request::Subscribe(EventType::PrivMsg));
+                let event = json!({"event": "PRIVMSG", "params": ["local", "user", "#a", "hi"]});
+                write_json(&mut core, event).await;
+                write_json(&mut core, json!({"did": "subscribe", "success": true})).await;
+                core
+            });
+
+            assert!(subscribe.await.unwrap().has_success());
+            let event = poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await;
+            assert_eq!(&event.unwrap()[3], "hi");
+
+            drop(core.await.unwrap());
+            assert!(events.next_event().await.is_none());
+        });
+    }
+
+    #[test]
+    fn rejected_request_is_an_error() {
+        run(async {
+            let (client, mut core) = io::duplex(1024);
+            let (dazeus, _events) = AsyncDaZeus::new(client);
+            let core = tokio::spawn(async move {
+                read_request(&mut core).await;
+                let response = json!({"did": "message", "success": false, "reason": "nonet"});
+                write_json(&mut core, response).await;
+                core
+            });
+
+            match dazeus.message("nonet", "#a", "hello").await {
+                Err(Error::CoreRejected { reason, .. }) => assert_eq!(reason, "nonet"),
+                other => panic!("Unexpected result: {:?}", other),
+            }
+            core.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn lost_connection_fails_requests() {
+        run(async {
+            let (client, mut core) = io::duplex(1024);
+            let (dazeus, _events) = AsyncDaZeus::new(client);
+            let nick = dazeus.send(Request::Nick("local".to_string()));
+            tokio::spawn(async move {
+                read_request(&mut core).await;
+            });
+
+            match nick.await {
+                Err(Error::IoError(ref e)) => assert_eq!(e.kind(), ErrorKind::UnexpectedEof),
+                other => panic!("Unexpected result: {:?}", other),
+            }
+            assert!(dazeus.send(Request::Networks).await.is_err());
+        });
+    }
+}
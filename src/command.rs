@@ -0,0 +1,464 @@
+use super::error::CommandParseError;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// The type of value that a command argument accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgType {
+    /// Any text (a single word, or a quoted string).
+    Text,
+    /// A whole number, such as `42` or `-3`.
+    Integer,
+    /// Any number, such as `3.14`.
+    Number,
+}
+
+impl ArgType {
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            ArgType::Text => true,
+            ArgType::Integer => i64::from_str(value).is_ok(),
+            ArgType::Number => f64::from_str(value).is_ok(),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ArgType::Text => "text",
+            ArgType::Integer => "a whole number",
+            ArgType::Number => "a number",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ArgSpec {
+    name: String,
+    kind: ArgType,
+    required: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OptionSpec {
+    name: String,
+    kind: Option<ArgType>,
+}
+
+/// A description of the arguments accepted by a command.
+///
+/// A command consists of positional arguments (which may be required or optional), flags and
+/// options (given as `--name` and `--name=value` or `--name value` respectively) and optionally
+/// a trailing argument that captures the rest of the line. Arguments containing spaces can be
+/// given by quoting them, such as `"foo bar"`.
+///
+/// Use `DaZeus::subscribe_parsed_command()` to listen for a command described this way.
+///
+/// # Example
+/// ```
+/// # use dazeus::*;
+/// let spec = CommandSpec::new("remind")
+///     .help("Remind someone of something")
+///     .arg("who", ArgType::Text)
+///     .arg("minutes", ArgType::Integer)
+///     .flag("private")
+///     .rest("message");
+///
+/// let args = spec.parse("\"Mr Example\" 5 --private take out the trash").unwrap();
+/// assert_eq!(args.get("who"), Some("Mr Example"));
+/// assert_eq!(args.get_as::<u32>("minutes"), Some(5));
+/// assert!(args.has_flag("private"));
+/// assert_eq!(args.get("message"), Some("take out the trash"));
+/// assert_eq!(spec.usage(), "remind <who> <minutes> [--private] <message...>");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSpec {
+    name: String,
+    help: Option<String>,
+    args: Vec<ArgSpec>,
+    options: Vec<OptionSpec>,
+    rest: Option<ArgSpec>,
+}
+
+impl CommandSpec {
+    /// Create a new command description for the command with the given name.
+    pub fn new(name: &str) -> CommandSpec {
+        CommandSpec {
+            name: name.to_string(),
+            help: None,
+            args: Vec::new(),
+            options: Vec::new(),
+            rest: None,
+        }
+    }
+
+    /// Set a short description of what the command does.
+    pub fn help(mut self, help: &str) -> CommandSpec {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// Add a required positional argument.
+    pub fn arg(mut self, name: &str, kind: ArgType) -> CommandSpec {
+        self.args.push(ArgSpec {
+            name: name.to_string(),
+            kind,
+            required: true,
+        });
+        self
+    }
+
+    /// Add an optional positional argument.
+    ///
+    /// Optional arguments are filled after all required arguments, in the order they were added.
+    pub fn optional_arg(mut self, name: &str, kind: ArgType) -> CommandSpec {
+        self.args.push(ArgSpec {
+            name: name.to_string(),
+            kind,
+            required: false,
+        });
+        self
+    }
+
+    /// Add a flag, which is given as `--name`.
+    pub fn flag(mut self, name: &str) -> CommandSpec {
+        self.options.push(OptionSpec {
+            name: name.to_string(),
+            kind: None,
+        });
+        self
+    }
+
+    /// Add an option with a value, which is given as `--name=value` or `--name value`.
+    pub fn option(mut self, name: &str, kind: ArgType) -> CommandSpec {
+        self.options.push(OptionSpec {
+            name: name.to_string(),
+            kind: Some(kind),
+        });
+        self
+    }
+
+    /// Add a required trailing argument that contains the rest of the line, as typed.
+    pub fn rest(mut self, name: &str) -> CommandSpec {
+        self.rest = Some(ArgSpec {
+            name: name.to_string(),
+            kind: ArgType::Text,
+            required: true,
+        });
+        self
+    }
+
+    /// Add an optional trailing argument that contains the rest of the line, as typed.
+    pub fn optional_rest(mut self, name: &str) -> CommandSpec {
+        self.rest = Some(ArgSpec {
+            name: name.to_string(),
+            kind: ArgType::Text,
+            required: false,
+        });
+        self
+    }
+
+    /// Retrieve the name of the command.
+    pub fn name(&self) -> &str {
+        &self.name[..]
+    }
+
+    /// Retrieve the description of what the command does (if any).
+    pub fn description(&self) -> Option<&str> {
+        self.help.as_ref().map(|h| &h[..])
+    }
+
+    /// Retrieve a usage line for the command, such as `remind <who> [--private] <message...>`.
+    pub fn usage(&self) -> String {
+        let mut parts = vec![self.name.clone()];
+        let mut args = self.args.iter().filter(|a| a.required).collect::<Vec<_>>();
+        args.extend(self.args.iter().filter(|a| !a.required));
+        for arg in args {
+            parts.push(if arg.required {
+                format!("<{}>", arg.name)
+            } else {
+                format!("[{}]", arg.name)
+            });
+        }
+        for option in self.options.iter() {
+            parts.push(match option.kind {
+                None => format!("[--{}]", option.name),
+                Some(_) => format!("[--{}=<{}>]", option.name, option.name),
+            });
+        }
+        if let Some(ref rest) = self.rest {
+            parts.push(if rest.required {
+                format!("<{}...>", rest.name)
+            } else {
+                format!("[{}...]", rest.name)
+            });
+        }
+        parts.join(" ")
+    }
+
+    /// Parse the arguments given to the command.
+    ///
+    /// The input should be the text following the command name, which is the fifth parameter
+    /// of a command event (`evt[4]`).
+    pub fn parse(&self, input: &str) -> Result<CommandArgs, CommandParseError> {
+        let mut parsed = CommandArgs::new();
+        let mut positional = Vec::new();
+        let mut tokens = Tokenizer::new(input);
+        let mut only_positional = false;
+
+        let max_positional = self.args.len();
+        while let Some(token) = tokens.next_token()? {
+            if !only_positional && !token.quoted && token.value == "--" {
+                only_positional = true;
+            } else if !only_positional && !token.quoted && token.value.starts_with("--") {
+                self.parse_option(&token.value[2..], &mut tokens, &mut parsed)?;
+            } else if positional.len() < max_positional {
+                positional.push(token.value);
+            } else if self.rest.is_some() {
+                let rest = self.rest.as_ref().unwrap();
+                parsed.values.insert(
+                    rest.name.clone(),
+                    input[token.start..].trim_end().to_string(),
+                );
+                break;
+            } else {
+                return Err(CommandParseError::new(&format!(
+                    "Unexpected argument '{}'",
+                    token.value
+                )));
+            }
+        }
+
+        // required arguments are filled first, optional ones take whatever is left
+        let required = self.args.iter().filter(|a| a.required).count();
+        if positional.len() < required {
+            let missing = self
+                .args
+                .iter()
+                .filter(|a| a.required)
+                .nth(positional.len());
+            return Err(CommandParseError::new(&format!(
+                "Missing argument <{}>",
+                missing.unwrap().name
+            )));
+        }
+        let mut values = positional.into_iter();
+        let args = self.args.iter().filter(|a| a.required);
+        let args = args.chain(self.args.iter().filter(|a| !a.required));
+        for (arg, value) in args.zip(&mut values) {
+            check_type(&arg.name, arg.kind, &value)?;
+            parsed.values.insert(arg.name.clone(), value);
+        }
+
+        if let Some(ref rest) = self.rest {
+            if rest.required && !parsed.values.contains_key(&rest.name) {
+                return Err(CommandParseError::new(&format!(
+                    "Missing argument <{}...>",
+                    rest.name
+                )));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn parse_option(
+        &self,
+        option: &str,
+        tokens: &mut Tokenizer,
+        parsed: &mut CommandArgs,
+    ) -> Result<(), CommandParseError> {
+        let (name, value) = match option.find('=') {
+            Some(idx) => (&option[..idx], Some(option[idx + 1..].to_string())),
+            None => (option, None),
+        };
+
+        let spec = match self.options.iter().find(|o| o.name == name) {
+            Some(spec) => spec,
+            None => {
+                return Err(CommandParseError::new(&format!(
+                    "Unknown option --{}",
+                    name
+                )))
+            }
+        };
+
+        match (spec.kind, value) {
+            (None, None) => {
+                parsed.flags.insert(spec.name.clone());
+            }
+            (None, Some(_)) => {
+                return Err(CommandParseError::new(&format!(
+                    "Option --{} does not take a value",
+                    name
+                )));
+            }
+            (Some(kind), value) => {
+                let value = match value {
+                    Some(value) => value,
+                    None => match tokens.next_token()? {
+                        Some(token) => token.value,
+                        None => {
+                            return Err(CommandParseError::new(&format!(
+                                "Missing value for option --{}",
+                                name
+                            )));
+                        }
+                    },
+                };
+                check_type(&spec.name, kind, &value)?;
+                parsed.values.insert(spec.name.clone(), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_type(name: &str, kind: ArgType, value: &str) -> Result<(), CommandParseError> {
+    if kind.accepts(value) {
+        Ok(())
+    } else {
+        Err(CommandParseError::new(&format!(
+            "Argument <{}> should be {}",
+            name,
+            kind.describe()
+        )))
+    }
+}
+
+/// The arguments given to a command, as parsed by a `CommandSpec`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandArgs {
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl CommandArgs {
+    fn new() -> CommandArgs {
+        CommandArgs::default()
+    }
+
+    /// Retrieve the value of an argument or option.
+    ///
+    /// Returns `None` if an optional argument or option was not given.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|v| &v[..])
+    }
+
+    /// Retrieve the value of an argument or option, converted to some type.
+    ///
+    /// Returns `None` if the argument was not given, or if it could not be converted.
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name).and_then(|v| T::from_str(v).ok())
+    }
+
+    /// Check whether a flag was given.
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+}
+
+struct Token {
+    value: String,
+    start: usize,
+    quoted: bool,
+}
+
+/// Splits a string into words, keeping quoted strings together.
+struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Tokenizer<'a> {
+        Tokenizer { input, pos: 0 }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, CommandParseError> {
+        let remaining = &self.input[self.pos..];
+        let start = self.pos + (remaining.len() - remaining.trim_start().len());
+        if start == self.input.len() {
+            self.pos = start;
+            return Ok(None);
+        }
+
+        let mut value = String::new();
+        let mut in_quotes = false;
+        let mut quoted = false;
+        let mut escaped = false;
+        let mut end = self.input.len();
+        for (idx, c) in self.input[start..].char_indices() {
+            if escaped {
+                value.push(c);
+                escaped = false;
+            } else if c == '\\' && in_quotes {
+                escaped = true;
+            } else if c == '"' {
+                in_quotes = !in_quotes;
+                quoted = true;
+            } else if !in_quotes && c.is_whitespace() {
+                end = start + idx;
+                break;
+            } else {
+                value.push(c);
+            }
+        }
+
+        if in_quotes {
+            return Err(CommandParseError::new("Unterminated quoted string"));
+        }
+
+        self.pos = end;
+        Ok(Some(Token {
+            value,
+            start,
+            quoted,
+        }))
+    }
+}
+
+/// Keeps track of the commands a plugin listens to, for generating help.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CommandRegistry {
+    commands: Rc<RefCell<Vec<CommandSpec>>>,
+}
+
+impl CommandRegistry {
+    /// Register a command, replacing any earlier description of the same command.
+    pub fn register(&self, spec: CommandSpec) {
+        let mut commands = self.commands.borrow_mut();
+        commands.retain(|c| c.name != spec.name);
+        commands.push(spec);
+    }
+
+    /// Register a command by name only, unless it has been registered before.
+    pub fn register_name(&self, name: &str) {
+        let mut commands = self.commands.borrow_mut();
+        if !commands.iter().any(|c| c.name == name) {
+            commands.push(CommandSpec::new(name));
+        }
+    }
+
+    /// Find the description of a command.
+    pub fn find(&self, name: &str) -> Option<CommandSpec> {
+        self.commands
+            .borrow()
+            .iter()
+            .find(|c| c.name == name)
+            .cloned()
+    }
+
+    /// Retrieve the names of all registered commands, in alphabetical order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .commands
+            .borrow()
+            .iter()
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
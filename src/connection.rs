@@ -1,6 +1,7 @@
 use std::io::{Error, ErrorKind, Read, Result, Write};
 use std::net::TcpStream;
 use std::str::FromStr;
+use std::time::Duration;
 use unix_socket::UnixStream;
 
 /// A connection enum that encapsulates TCP and Unix sockets.
@@ -29,6 +30,58 @@ impl Connection {
             },
         }
     }
+
+    /// Set the maximum time to wait for data from the DaZeus core.
+    ///
+    /// By default (or with `None`) reading blocks until data arrives. When a timeout is set,
+    /// reading regularly stops waiting, which allows `DaZeus::listen()` to send heartbeats (see
+    /// `DaZeus::set_heartbeat()`) and do other work in between events. Waiting for a response to
+    /// a request continues until the request timeout has passed (see
+    /// `DaZeus::set_request_timeout()`), which keeps a plugin from hanging forever when the core
+    /// stops responding.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// let conn = Connection::from_str("tcp:localhost:1234").unwrap();
+    /// conn.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    /// let dazeus = DaZeus::new(conn);
+    /// ```
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match *self {
+            Connection::Unix(ref stream) => stream.set_read_timeout(timeout),
+            Connection::Tcp(ref stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    /// Set the maximum time to wait while sending data to the DaZeus core.
+    ///
+    /// By default (or with `None`) writing blocks until all data has been sent. Sending a request
+    /// that takes longer results in an `Error::Timeout`.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match *self {
+            Connection::Unix(ref stream) => stream.set_write_timeout(timeout),
+            Connection::Tcp(ref stream) => stream.set_write_timeout(timeout),
+        }
+    }
+
+    /// Retrieve the read timeout of the connection, see `Connection::set_read_timeout()`.
+    pub fn read_timeout(&self) -> Result<Option<Duration>> {
+        match *self {
+            Connection::Unix(ref stream) => stream.read_timeout(),
+            Connection::Tcp(ref stream) => stream.read_timeout(),
+        }
+    }
+
+    /// Retrieve the write timeout of the connection, see `Connection::set_write_timeout()`.
+    pub fn write_timeout(&self) -> Result<Option<Duration>> {
+        match *self {
+            Connection::Unix(ref stream) => stream.write_timeout(),
+            Connection::Tcp(ref stream) => stream.write_timeout(),
+        }
+    }
 }
 
 impl FromStr for Connection {
@@ -1,16 +1,103 @@
-use super::error::{Error, ReceiveError};
+use super::command::{ArgType, CommandArgs, CommandRegistry, CommandSpec};
+use super::error::{Error, InvalidJsonError, ReceiveError, TimeoutError};
 use super::event::{Event, EventType};
+use super::filter::EventFilter;
 use super::handler::{Handler, Message};
-use super::listener::{Listener, ListenerHandle};
+use super::listener::{Flow, Listener, ListenerHandle};
+use super::properties::PropertyStore;
+use super::ratelimit::{RateLimit, RateLimiter};
+use super::reconnect::ReconnectPolicy;
+use super::remote::{Call, DaZeusHandle, Listening};
 use super::request::{ConfigGroup, Request};
 use super::response::Response;
+use super::schedule::{Scheduler, TaskHandle};
 use super::scope::Scope;
-use std::cell::RefCell;
-use std::io::{Read, Write};
-
+use super::stop::StopHandle;
+use super::typed::TypedEvent;
+use log::warn;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A ticket identifying a request that is waiting for its response.
+type Ticket = u64;
+
+/// Keeps track of the requests that are waiting for a response.
+///
+/// The DaZeus core answers requests in the order in which they were sent, so every response that
+/// is received belongs to the oldest request that is still in flight. Responses are stored by
+/// ticket until the caller waiting for them picks them up, which allows requests to be sent from
+/// listener callbacks while another request is still waiting for its response.
 struct ResponseQueue {
-    pub responses: Vec<Response>,
-    pub expecting: u64,
+    pending: VecDeque<Ticket>,
+    received: HashMap<Ticket, Response>,
+    abandoned: HashSet<Ticket>,
+    next_ticket: Ticket,
+}
+
+impl ResponseQueue {
+    fn new() -> ResponseQueue {
+        ResponseQueue {
+            pending: VecDeque::new(),
+            received: HashMap::new(),
+            abandoned: HashSet::new(),
+            next_ticket: 1,
+        }
+    }
+
+    /// Register a new request that is now in flight.
+    fn issue(&mut self) -> Ticket {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.pending.push_back(ticket);
+        ticket
+    }
+
+    /// Assign a received response to the oldest request in flight.
+    fn receive(&mut self, response: Response) -> Result<(), ReceiveError> {
+        match self.pending.pop_front() {
+            Some(ticket) => {
+                if !self.abandoned.remove(&ticket) {
+                    self.received.insert(ticket, response);
+                }
+                Ok(())
+            }
+            None => Err(ReceiveError::new()),
+        }
+    }
+
+    /// Take the response for some ticket if it has been received.
+    fn take(&mut self, ticket: Ticket) -> Option<Response> {
+        self.received.remove(&ticket)
+    }
+
+    /// Stop waiting for the response to some ticket, for example after a timeout.
+    ///
+    /// The request is still in flight, so its response is dropped when it arrives.
+    fn abandon(&mut self, ticket: Ticket) {
+        if self.received.remove(&ticket).is_none() && self.pending.contains(&ticket) {
+            self.abandoned.insert(ticket);
+        }
+    }
+
+    /// Forget about all requests in flight.
+    fn clear(&mut self) {
+        self.pending.clear();
+        self.received.clear();
+        self.abandoned.clear();
+    }
+}
+
+struct Reconnector<'a, T> {
+    policy: ReconnectPolicy,
+    #[allow(clippy::type_complexity)]
+    connect: RefCell<Box<dyn FnMut() -> io::Result<T> + 'a>>,
 }
 
 /// The base DaZeus struct.
@@ -20,8 +107,29 @@ struct ResponseQueue {
 pub struct DaZeus<'a, T> {
     handler: RefCell<Handler<T>>,
     listeners: Vec<Listener<'a>>,
+    deferred: RefCell<VecDeque<(Event, usize)>>,
+    dispatching: RefCell<Vec<usize>>,
     current_handle: u64,
     queue: RefCell<ResponseQueue>,
+    reconnector: Option<Reconnector<'a, T>>,
+    handshake: RefCell<Option<Request>>,
+    commands: CommandRegistry,
+    permission_denied: Rc<RefCell<Option<String>>>,
+    event_timeout: Duration,
+    request_timeout: Duration,
+    flush_timeout: Duration,
+    heartbeat: Option<Duration>,
+    last_activity: Cell<Instant>,
+    limiter: RefCell<RateLimiter>,
+    nicks: RefCell<HashMap<String, String>>,
+    tracking_nicks: Cell<bool>,
+    calls: (Sender<Call>, Receiver<Call>),
+    listening: Listening,
+    scheduler: RefCell<Scheduler<'a>>,
+    stop: StopHandle,
+    unsubscribe_on_stop: bool,
+    #[cfg(feature = "signals")]
+    stop_on_signals: bool,
 }
 
 impl<'a, T> DaZeus<'a, T>
@@ -33,141 +141,1639 @@ where
         DaZeus {
             handler: RefCell::new(Handler::new(conn)),
             listeners: Vec::new(),
+            deferred: RefCell::new(VecDeque::new()),
+            dispatching: RefCell::new(Vec::new()),
             current_handle: 1,
-            queue: RefCell::new(ResponseQueue {
-                responses: Vec::new(),
-                expecting: 0,
-            }),
+            queue: RefCell::new(ResponseQueue::new()),
+            reconnector: None,
+            handshake: RefCell::new(None),
+            commands: CommandRegistry::default(),
+            permission_denied: Rc::new(RefCell::new(Some("Permission denied".to_string()))),
+            event_timeout: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(30),
+            flush_timeout: Duration::from_secs(10),
+            heartbeat: None,
+            last_activity: Cell::new(Instant::now()),
+            limiter: RefCell::new(RateLimiter::new()),
+            nicks: RefCell::new(HashMap::new()),
+            tracking_nicks: Cell::new(false),
+            calls: channel(),
+            listening: Listening::new(),
+            scheduler: RefCell::new(Scheduler::new()),
+            stop: StopHandle::new(),
+            unsubscribe_on_stop: false,
+            #[cfg(feature = "signals")]
+            stop_on_signals: false,
+        }
+    }
+
+    /// Enable automatically reconnecting to the DaZeus core when the connection is lost.
+    ///
+    /// The `connect` function is called to establish a new connection, typically this will dial
+    /// the same address as the original connection. Once reconnected, the last handshake is sent
+    /// again and all event types and commands that listeners are registered for are subscribed
+    /// to again, so listeners keep receiving events as if nothing happened.
+    ///
+    /// Reconnecting only happens while waiting for events in `DaZeus::listen()`, requests that
+    /// fail because the connection was lost still return an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// let socket = "unix:/tmp/dazeus.sock";
+    /// let mut dazeus = DaZeus::new(Connection::from_str(socket).unwrap());
+    /// dazeus.set_reconnect(ReconnectPolicy::default(), move || Connection::from_str(socket));
+    /// ```
+    pub fn set_reconnect<F>(&mut self, policy: ReconnectPolicy, connect: F)
+    where
+        F: FnMut() -> io::Result<T> + 'a,
+    {
+        self.reconnector = Some(Reconnector {
+            policy,
+            connect: RefCell::new(Box::new(connect)),
+        });
+    }
+
+    /// Set how long `DaZeusClient::whois()` and `DaZeusClient::names()` wait for their answer.
+    ///
+    /// The default is 30 seconds.
+    pub fn set_event_timeout(&mut self, timeout: Duration) {
+        self.event_timeout = timeout;
+    }
+
+    /// Set how long to wait for the core to respond to a request.
+    ///
+    /// This only has an effect if the connection has a read timeout (see
+    /// `Connection::set_read_timeout()`), as otherwise reading blocks until the core responds.
+    /// The read timeout can then be kept short, so `DaZeus::listen()` wakes up often to run
+    /// scheduled tasks and the like, while the core still gets this long to respond. If it does
+    /// not, the request fails with an `Error::Timeout`. The default is 30 seconds.
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = timeout;
+    }
+
+    /// Periodically check whether the DaZeus core is still responding while listening.
+    ///
+    /// When nothing has been received from the core for the given interval, `DaZeus::listen()`
+    /// sends a cheap request to the core. If the core does not respond, an `Error::Timeout` is
+    /// returned (or the connection is re-established, if reconnecting was enabled using
+    /// `DaZeus::set_reconnect()`).
+    ///
+    /// Heartbeats can only be sent when waiting for events stops every now and then, so the
+    /// connection should have a read timeout that is shorter than the interval, see
+    /// `Connection::set_read_timeout()`. The core has to respond to a heartbeat within the request
+    /// timeout, see `DaZeus::set_request_timeout()`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+    /// conn.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    /// let mut dazeus = DaZeus::new(conn);
+    /// dazeus.set_heartbeat(Some(Duration::from_secs(60)));
+    /// dazeus.listen();
+    /// ```
+    pub fn set_heartbeat(&mut self, interval: Option<Duration>) {
+        self.heartbeat = interval;
+    }
+
+    /// Limit the rate at which messages are sent, to prevent the bot from flooding IRC.
+    ///
+    /// Messages, notices, actions and CTCPs that exceed the rate limit for their network and
+    /// target are queued instead of being sent, and the request returns a response for which
+    /// `Response::has_success()` is true and that has the `queued` property set. Queued messages
+    /// are sent by `DaZeus::listen()` as soon as the rate limit allows, so the connection should
+    /// have a read timeout to make sure `listen()` does not wait for the next event before sending
+    /// them (see `Connection::set_read_timeout()`).
+    ///
+    /// Passing `None` disables rate limiting, any messages that are still queued are sent the next
+    /// time `listen()` gets the chance.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+    /// conn.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    /// let mut dazeus = DaZeus::new(conn);
+    /// dazeus.set_rate_limit(Some(RateLimit::new(3, Duration::from_secs(1))));
+    /// ```
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.limiter.borrow_mut().set_limit(limit);
+    }
+
+    /// Limit the rate at which messages are sent to all targets on a network together.
+    ///
+    /// The limit of `DaZeus::set_rate_limit()` applies to every target separately, so a plugin
+    /// sending a message to many channels at once could still flood the network. With a network
+    /// rate limit, a message is only sent when both its target and its network allow it. Messages
+    /// that are held back by the network limit are sent in turns for the targets on the network.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+    /// conn.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+    /// let mut dazeus = DaZeus::new(conn);
+    /// dazeus.set_rate_limit(Some(RateLimit::new(3, Duration::from_secs(1))));
+    /// dazeus.set_network_rate_limit(Some(RateLimit::new(10, Duration::from_millis(500))));
+    /// ```
+    pub fn set_network_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.limiter.borrow_mut().set_network_limit(limit);
+    }
+
+    /// Set how long stopping `DaZeus::listen()` may wait for queued messages to be sent.
+    ///
+    /// When listening stops, messages that are queued because of the rate limit are sent first.
+    /// Messages that could not be sent in time are kept in the queue (a warning is logged), and
+    /// are only sent if `listen()` is called again. The default is 10 seconds.
+    pub fn set_flush_timeout(&mut self, timeout: Duration) {
+        self.flush_timeout = timeout;
+    }
+
+    /// Retrieve the number of messages that are queued because of the rate limit.
+    pub fn queued_messages(&self) -> usize {
+        self.limiter.borrow().queued()
+    }
+
+    /// Retrieve the number of messages to some target that are queued because of the rate limit.
+    pub fn queued_messages_for(&self, network: &str, target: &str) -> usize {
+        self.limiter.borrow().queued_for(network, target)
+    }
+
+    /// Send the queued messages that the rate limit allows to be sent now.
+    fn send_queued(&self) -> Result<(), Error> {
+        let ready = self.limiter.borrow_mut().take_ready();
+        for request in ready {
+            let description = format!("{:?}", request);
+            let response = self.send_now(request)?;
+            if !response.has_success() {
+                warn!(
+                    "Queued request {} was rejected: {}",
+                    description,
+                    response.get_str_or("reason", "")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a handle for sending requests from other threads.
+    ///
+    /// See `DaZeusHandle` for details.
+    pub fn handle(&self) -> DaZeusHandle {
+        DaZeusHandle::new(
+            self.calls.0.clone(),
+            self.listening.clone(),
+            self.stop.clone(),
+        )
+    }
+
+    /// Handle all calls made using a `DaZeusHandle` since the last time.
+    fn handle_calls(&self) {
+        while let Ok(call) = self.calls.1.try_recv() {
+            // the handle may have given up waiting, in which case the result is not needed
+            match call {
+                Call::Send(request, reply) => {
+                    let _ = reply.send(self.try_send(request));
+                }
+                Call::Whois(network, nick, reply) => {
+                    let _ = reply.send(self.request_event(
+                        Some(Request::Whois(network.clone(), nick.clone())),
+                        EventType::Whois,
+                        |evt| answers_whois(evt, &network, &nick),
+                        self.event_timeout,
+                    ));
+                }
+                Call::Names(network, channel, reply) => {
+                    let _ = reply.send(self.request_event(
+                        Some(Request::Names(network.clone(), channel.clone())),
+                        EventType::Names,
+                        |evt| answers_names(evt, &network, &channel),
+                        self.event_timeout,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Run a callback once at the given time.
+    ///
+    /// Scheduled tasks are run by `DaZeus::listen()`, which can only check whether a task is due
+    /// when it receives something from the core. The connection should therefore have a read
+    /// timeout (see `Connection::set_read_timeout()`), which determines how precisely tasks are
+    /// run on time.
+    ///
+    /// The returned handle can be used to cancel the task before it has run.
+    pub fn schedule_at<F>(&self, time: SystemTime, callback: F) -> TaskHandle
+    where
+        F: FnMut(&dyn DaZeusClient) + 'a,
+    {
+        let delay = time
+            .duration_since(SystemTime::now())
+            .unwrap_or_else(|_| Duration::from_secs(0));
+        self.schedule_in(delay, callback)
+    }
+
+    /// Run a callback once after some time has passed.
+    ///
+    /// See `DaZeus::schedule_at()` for details.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+    /// conn.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    /// let dazeus = DaZeus::new(conn);
+    ///
+    /// dazeus.schedule_in(Duration::from_secs(300), |dazeus| {
+    ///     dazeus.message("freenode", "#dazeus", "Your tea is ready!");
+    /// });
+    /// dazeus.listen();
+    /// ```
+    pub fn schedule_in<F>(&self, delay: Duration, callback: F) -> TaskHandle
+    where
+        F: FnMut(&dyn DaZeusClient) + 'a,
+    {
+        self.scheduler
+            .borrow_mut()
+            .add(Instant::now() + delay, None, callback)
+    }
+
+    /// Run a callback repeatedly, waiting for the given interval between runs.
+    ///
+    /// The first run is after one interval has passed. Runs that were missed (for example because
+    /// another callback took a long time) are not caught up on. See `DaZeus::schedule_at()` for
+    /// details.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+    /// conn.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    /// let dazeus = DaZeus::new(conn);
+    ///
+    /// let mut count = 0;
+    /// dazeus.schedule_every(Duration::from_secs(3600), move |dazeus| {
+    ///     count += 1;
+    ///     dazeus.message("freenode", "#dazeus", &format!("{} hours have passed", count));
+    /// });
+    /// dazeus.listen();
+    /// ```
+    pub fn schedule_every<F>(&self, interval: Duration, callback: F) -> TaskHandle
+    where
+        F: FnMut(&dyn DaZeusClient) + 'a,
+    {
+        self.scheduler
+            .borrow_mut()
+            .add(Instant::now() + interval, Some(interval), callback)
+    }
+
+    /// Run all scheduled tasks that are due.
+    fn run_due_tasks(&self) {
+        let due = self.scheduler.borrow_mut().take_due();
+        for mut task in due {
+            task.call(self);
+            self.scheduler.borrow_mut().reschedule(task);
+        }
+    }
+
+    /// Create a property store that is namespaced by the name of this plugin.
+    ///
+    /// The name given in the last handshake is used as the prefix of the store, and the store
+    /// uses `Scope::any()` as its default scope. Returns `None` if no handshake has been sent yet.
+    pub fn property_store(&self) -> Option<PropertyStore> {
+        match *self.handshake.borrow() {
+            Some(Request::Handshake(ref name, _, _)) => {
+                Some(PropertyStore::new(name, Scope::any()))
+            }
+            _ => None,
         }
     }
 
     /// Loop wait for messages to receive in a blocking way.
+    ///
+    /// If reconnecting was enabled using `DaZeus::set_reconnect()`, a lost connection is
+    /// re-established, otherwise the error is returned.
+    ///
+    /// Listening continues until it is stopped using a `StopHandle` or `DaZeusClient::stop()`.
+    /// Before returning `Ok(())`, all messages that were queued because of the rate limit are
+    /// sent, and the event subscriptions are removed if `DaZeus::set_unsubscribe_on_stop()` was
+    /// enabled.
     pub fn listen(&self) -> Result<(), Error> {
+        let _listening = self.listening.start(&self.calls.1);
         loop {
-            self.try_next_event()?;
+            if self.is_stopping() {
+                return self.shutdown();
+            }
+
+            let result = match self.try_next_event() {
+                // no event arrived before the read timeout of the connection
+                Err(Error::Timeout(_)) => self.send_heartbeat(),
+                // a signal arrived while waiting for an event
+                Err(Error::IoError(ref e)) if e.kind() == io::ErrorKind::Interrupted => Ok(()),
+                other => other.map(|_| ()),
+            }
+            .and_then(|_| {
+                self.handle_calls();
+                self.run_due_tasks();
+                self.send_queued()
+            });
+
+            match result {
+                Ok(()) => (),
+                Err(Error::IoError(ref e)) if self.reconnector.is_some() => {
+                    warn!("Lost connection to DaZeus: {}", e);
+                    self.reconnect()?;
+                }
+                Err(Error::Timeout(ref e)) if self.reconnector.is_some() => {
+                    warn!("DaZeus is not responding: {:?}", e);
+                    self.reconnect()?;
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
-    fn next_response(&self) -> Result<Response, Error> {
+    /// Create a handle that can be used to stop `DaZeus::listen()`, also from other threads.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Remove all event subscriptions from the core when `DaZeus::listen()` is stopped.
+    ///
+    /// The listeners themselves are kept, so they are subscribed to again if the connection is
+    /// re-established. Commands can't be unsubscribed from.
+    pub fn set_unsubscribe_on_stop(&mut self, unsubscribe: bool) {
+        self.unsubscribe_on_stop = unsubscribe;
+    }
+
+    /// Stop `DaZeus::listen()` when the process receives SIGINT or SIGTERM.
+    ///
+    /// This replaces any existing handlers for these signals. Blocking reads are interrupted by
+    /// the signals, so listening stops right away even without a read timeout.
+    #[cfg(feature = "signals")]
+    pub fn stop_on_signals(&mut self) -> io::Result<()> {
+        super::stop::catch_signals()?;
+        self.stop_on_signals = true;
+        Ok(())
+    }
+
+    fn is_stopping(&self) -> bool {
+        #[cfg(feature = "signals")]
         {
-            self.queue.borrow_mut().expecting += 1;
+            if self.stop_on_signals && super::stop::take_signal() {
+                self.stop.stop();
+            }
         }
+        self.stop.is_stopped()
+    }
+
+    /// Clean up after listening was stopped.
+    fn shutdown(&self) -> Result<(), Error> {
+        self.stop.reset();
+
+        let deadline = Instant::now() + self.flush_timeout;
         loop {
-            {
-                let mut queue = self.queue.borrow_mut();
-                if !queue.responses.is_empty() && queue.expecting == 0 {
-                    return Ok(queue.responses.pop().unwrap());
+            self.send_queued()?;
+            let delay = match self.limiter.borrow().next_ready() {
+                Some(delay) => delay,
+                None => break,
+            };
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if delay >= remaining {
+                warn!(
+                    "{} queued messages could not be sent before the flush timeout",
+                    self.queued_messages()
+                );
+                break;
+            }
+            thread::sleep(delay);
+        }
+
+        if self.unsubscribe_on_stop {
+            let mut events: Vec<EventType> = Vec::new();
+            for listener in self.listeners.iter() {
+                match listener.event {
+                    EventType::Command(_) => (),
+                    ref event if events.contains(event) => (),
+                    ref event => events.push(event.clone()),
                 }
             }
+            if self.tracking_nicks.get() && !events.contains(&EventType::Nick) {
+                events.push(EventType::Nick);
+            }
+            for event in events {
+                send_checked(self, Request::Unsubscribe(event))?;
+            }
+            self.forget_nicks();
+        }
+        Ok(())
+    }
 
-            let msg = { self.handler.borrow_mut().read() };
-            match msg? {
-                Message::Event(e) => self.handle_event(e),
-                Message::Response(r) => {
-                    let mut queue = self.queue.borrow_mut();
-                    queue.expecting -= 1;
+    /// Check whether the core is still responding if nothing was received for a while.
+    fn send_heartbeat(&self) -> Result<(), Error> {
+        match self.heartbeat {
+            Some(interval) if self.last_activity.get().elapsed() >= interval => {
+                send_checked(self, Request::Networks)?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
 
-                    if queue.expecting == 0 {
-                        return Ok(r);
-                    } else {
-                        queue.responses.push(r);
+    /// Try to re-establish the connection to DaZeus and restore the handshake and subscriptions.
+    fn reconnect(&self) -> Result<(), Error> {
+        let reconnector = match self.reconnector {
+            Some(ref reconnector) => reconnector,
+            None => return Ok(()),
+        };
+
+        let mut attempts = 0;
+        loop {
+            match self.try_reconnect(reconnector) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    if !reconnector.policy.allows_attempt(attempts + 1) {
+                        return Err(e);
                     }
+                    let delay = reconnector.policy.delay_after(attempts);
+                    warn!("Reconnecting failed: {}, retrying in {:?}", e, delay);
+                    thread::sleep(delay);
+                    attempts += 1;
                 }
             }
         }
     }
 
+    fn try_reconnect(&self, reconnector: &Reconnector<'a, T>) -> Result<(), Error> {
+        let conn = {
+            let mut connect = reconnector.connect.borrow_mut();
+            (*connect)()?
+        };
+        *self.handler.borrow_mut() = Handler::new(conn);
+        // responses still expected from the old connection will never arrive
+        self.queue.borrow_mut().clear();
+        self.forget_nicks();
+
+        let handshake = self.handshake.borrow().clone();
+        if let Some(request) = handshake {
+            send_checked(self, request)?;
+        }
+
+        let mut events: Vec<EventType> = Vec::new();
+        for listener in self.listeners.iter() {
+            if !events.contains(&listener.event) {
+                events.push(listener.event.clone());
+            }
+        }
+        for event in events {
+            send_checked(self, subscribe_request(event))?;
+        }
+        Ok(())
+    }
+
+    /// Send a request to the core (bypassing the rate limit) and wait for its response.
+    fn send_now(&self, request: Request) -> Result<Response, Error> {
+        if let Request::Handshake(_, _, _) = request {
+            *self.handshake.borrow_mut() = Some(request.clone());
+        }
+        self.handler.borrow_mut().write(request)?;
+        let ticket = self.queue.borrow_mut().issue();
+        let result = self.next_response(ticket);
+        if result.is_err() {
+            self.queue.borrow_mut().abandon(ticket);
+        }
+        result
+    }
+
+    /// Read the next message from the core.
+    fn read_message(&self) -> Result<Message, Error> {
+        let msg = { self.handler.borrow_mut().read() }?;
+        self.last_activity.set(Instant::now());
+        Ok(msg)
+    }
+
+    /// Wait for the response belonging to the given ticket, handling any events in the meantime.
+    fn next_response(&self, ticket: Ticket) -> Result<Response, Error> {
+        let deadline = Instant::now() + self.request_timeout;
+        loop {
+            if let Some(response) = self.queue.borrow_mut().take(ticket) {
+                return Ok(response);
+            }
+
+            match self.read_message() {
+                Ok(Message::Event(e)) => self.handle_event(e),
+                Ok(Message::Response(r)) => self.queue.borrow_mut().receive(r)?,
+                // the read timeout of the connection passed, keep waiting until the deadline
+                Err(Error::Timeout(_)) if Instant::now() < deadline => (),
+                Err(Error::Timeout(_)) => {
+                    return Err(TimeoutError::new("No response received from DaZeus").into())
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     fn try_next_event(&self) -> Result<Event, Error> {
-        let msg = { self.handler.borrow_mut().read() };
-        match msg? {
-            Message::Event(e) => {
-                self.handle_event(e.clone());
-                Ok(e)
+        loop {
+            match self.read_message()? {
+                Message::Event(e) => {
+                    self.handle_event(e.clone());
+                    return Ok(e);
+                }
+                // a late response to a request that timed out
+                Message::Response(r) => self.queue.borrow_mut().receive(r)?,
             }
-            Message::Response(_) => Err(Error::ReceiveError(ReceiveError::new())),
         }
     }
 
-    fn next_event(&self) -> Event {
-        match self.try_next_event() {
-            Ok(evt) => evt,
-            Err(e) => panic!("{}", e),
+    /// Wait for an event of the given type for which the predicate returns true (blocking).
+    ///
+    /// All events received in the meantime (including the one that is returned) are passed to
+    /// the listeners as usual. If no such event arrives within the timeout, an `Error::Timeout`
+    /// is returned. Note that the timeout is checked whenever a message is received from the
+    /// core, or when the read timeout of the connection passes (see
+    /// `Connection::set_read_timeout()`).
+    ///
+    /// If there is no listener for the event type, the event type is subscribed to for as long as
+    /// this method is waiting. This makes it the way to handle just the next matching event: unlike
+    /// a listener, nothing remains subscribed once it returns.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::time::Duration;
+    /// # let dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// dazeus.join("freenode", "#dazeus");
+    /// let joined = dazeus.try_wait_for_event(EventType::Join, |evt| &evt[2] == "#dazeus", Duration::from_secs(10));
+    /// ```
+    pub fn try_wait_for_event<P>(
+        &self,
+        event: EventType,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<Event, Error>
+    where
+        P: Fn(&Event) -> bool,
+    {
+        self.request_event(None, event, predicate, timeout)
+    }
+
+    /// Wait for an event of the given type for which the predicate returns true (blocking).
+    ///
+    /// Panics if no such event arrived within the timeout, see `DaZeus::try_wait_for_event()` for
+    /// a non-panicking alternative.
+    pub fn wait_for_event<P>(&self, event: EventType, predicate: P, timeout: Duration) -> Event
+    where
+        P: Fn(&Event) -> bool,
+    {
+        unwrap_or_panic(self.try_wait_for_event(event, predicate, timeout))
+    }
+
+    /// Send a request (if any) and wait for an event that answers it.
+    fn request_event<P>(
+        &self,
+        request: Option<Request>,
+        event: EventType,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<Event, Error>
+    where
+        P: Fn(&Event) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let subscribed = self.is_subscribed(&event);
+        if !subscribed {
+            send_checked(self, subscribe_request(event.clone()))?;
+        }
+
+        let result = match request {
+            Some(request) => send_checked(self, request)
+                .and_then(|_| self.next_matching_event(&event, predicate, deadline)),
+            None => self.next_matching_event(&event, predicate, deadline),
+        };
+
+        match event {
+            // we can't unsubscribe commands
+            EventType::Command(_) => (),
+            _ if subscribed => (),
+            _ => {
+                send_checked(self, Request::Unsubscribe(event))?;
+            }
+        }
+        result
+    }
+
+    fn next_matching_event<P>(
+        &self,
+        event: &EventType,
+        predicate: P,
+        deadline: Instant,
+    ) -> Result<Event, Error>
+    where
+        P: Fn(&Event) -> bool,
+    {
+        loop {
+            if Instant::now() >= deadline {
+                return Err(TimeoutError::new(&format!("No {} event received", event)).into());
+            }
+
+            match self.read_message() {
+                Ok(Message::Event(e)) => {
+                    self.handle_event(e.clone());
+                    if e.event == *event && predicate(&e) {
+                        return Ok(e);
+                    }
+                }
+                Ok(Message::Response(r)) => self.queue.borrow_mut().receive(r)?,
+                // the read timeout of the connection passed, check the deadline again
+                Err(Error::Timeout(_)) => (),
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /// Handle an event received by calling all event listeners listening for that event type.
+    ///
+    /// Listeners are called in order of priority, until one of them stops the event from being
+    /// passed on. An event that arrives while a listener is still handling an earlier event (for
+    /// example because it is waiting for a response) is only passed to the listeners before it,
+    /// which are done with the earlier event. Passing it on to the rest is deferred until the
+    /// earlier event has been handled, so every listener receives events in order.
     fn handle_event(&self, event: Event) {
-        for listener in self.listeners.iter() {
-            if listener.event == event.event {
-                listener.call(event.clone(), self);
+        self.track_nick(&event);
+        if self.deferred.borrow().is_empty() {
+            if let Some(position) = self.dispatch(&event, 0) {
+                self.deferred.borrow_mut().push_back((event, position));
+            }
+        } else {
+            // earlier events are still waiting to be passed on
+            self.deferred.borrow_mut().push_back((event, 0));
+        }
+        self.handle_deferred();
+    }
+
+    /// Keep the known nicks of the bot up to date, see `DaZeusClient::try_cached_nick()`.
+    fn track_nick(&self, event: &Event) {
+        match event.as_typed() {
+            Ok(TypedEvent::Nick(e)) => {
+                let mut nicks = self.nicks.borrow_mut();
+                if nicks.get(&e.network) == Some(&e.old_nick) {
+                    nicks.insert(e.network, e.new_nick);
+                }
+            }
+            // the bot may get another nick when the network is connected again
+            Ok(TypedEvent::Connect(e)) | Ok(TypedEvent::Disconnect(e)) => {
+                self.nicks.borrow_mut().remove(&e.network);
+            }
+            _ => (),
+        }
+    }
+
+    /// Forget the known nicks of the bot, which are no longer kept up to date.
+    fn forget_nicks(&self) {
+        self.nicks.borrow_mut().clear();
+        self.tracking_nicks.set(false);
+    }
+
+    /// Whether the core should keep sending events of the given type, either for a listener or to
+    /// keep track of the nick of the bot.
+    fn is_subscribed(&self, event: &EventType) -> bool {
+        self.listeners.iter().any(|l| l.event == *event)
+            || (*event == EventType::Nick && self.tracking_nicks.get())
+    }
+
+    /// Pass an event to the listeners, starting at the given position.
+    ///
+    /// Returns the position at which passing on the event was paused, because the listener at
+    /// that position or one after it is still handling an earlier event.
+    fn dispatch(&self, event: &Event, start: usize) -> Option<usize> {
+        let limit = self
+            .dispatching
+            .borrow()
+            .iter()
+            .min()
+            .cloned()
+            .unwrap_or(usize::MAX);
+        for (position, listener) in self.listeners.iter().enumerate().skip(start) {
+            if position >= limit {
+                return Some(position);
+            }
+            if !listener.matches(event, self) {
+                continue;
+            }
+
+            self.dispatching.borrow_mut().push(position);
+            let flow = listener.call(event.clone(), self);
+            self.dispatching.borrow_mut().pop();
+            match flow {
+                Some(Flow::Continue) => (),
+                Some(Flow::Stop) => return None,
+                // the callback is shared with a listener that is still busy
+                None => return Some(position),
+            }
+        }
+        None
+    }
+
+    /// Continue passing on deferred events, for as long as the listeners are done.
+    fn handle_deferred(&self) {
+        loop {
+            let (event, start) = match self.deferred.borrow_mut().pop_front() {
+                Some(deferred) => deferred,
+                None => return,
+            };
+
+            // if it has to wait again, it is continued when the busy listeners are done
+            if let Some(position) = self.dispatch(&event, start) {
+                self.deferred.borrow_mut().push_front((event, position));
+                return;
             }
         }
     }
 
     /// Subscribe to an event type and call the callback function every time such an event occurs.
+    pub fn try_subscribe<F>(
+        &mut self,
+        event: EventType,
+        callback: F,
+    ) -> Result<(ListenerHandle, Response), Error>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        let listener = self.new_listener(event, continue_after(callback));
+        self.try_add_listener(listener)
+    }
+
+    /// Subscribe to an event type and call the callback function every time such an event occurs.
+    ///
+    /// If DaZeus rejects the subscription, the listener is removed again and the returned response
+    /// indicates the failure. Panics if the request could not be sent, see
+    /// `DaZeus::try_subscribe()` for a non-panicking alternative.
     pub fn subscribe<F>(&mut self, event: EventType, callback: F) -> (ListenerHandle, Response)
     where
         F: FnMut(Event, &dyn DaZeusClient) + 'a,
     {
-        let request = match event {
-            EventType::Command(ref cmd) => Request::SubscribeCommand(cmd.clone(), None),
-            _ => Request::Subscribe(event.clone()),
-        };
+        unwrap_subscription(self.current_handle, self.try_subscribe(event, callback))
+    }
+
+    /// Subscribe to an event type and call the callback function for every such event that
+    /// matches the filter.
+    ///
+    /// See `EventFilter` for the conditions that can be used.
+    pub fn try_subscribe_filtered<F>(
+        &mut self,
+        event: EventType,
+        filter: EventFilter,
+        callback: F,
+    ) -> Result<(ListenerHandle, Response), Error>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        let mut listener = self.new_listener(event, continue_after(callback));
+        listener.filter = Some(filter);
+        self.try_add_listener(listener)
+    }
+
+    /// Subscribe to an event type and call the callback function for every such event that
+    /// matches the filter.
+    ///
+    /// Panics if the request could not be sent, see `DaZeus::try_subscribe_filtered()` for a
+    /// non-panicking alternative.
+    pub fn subscribe_filtered<F>(
+        &mut self,
+        event: EventType,
+        filter: EventFilter,
+        callback: F,
+    ) -> (ListenerHandle, Response)
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        unwrap_subscription(
+            self.current_handle,
+            self.try_subscribe_filtered(event, filter, callback),
+        )
+    }
+
+    /// Subscribe to every event type and call the callback function every time any event occurs.
+    ///
+    /// This subscribes to all event types returned by `EventType::all_events()`, so commands are
+    /// not included. All events are passed to the same callback, and the returned handle can be
+    /// used to unsubscribe from all of them at once.
+    pub fn try_subscribe_all<F>(&mut self, callback: F) -> Result<ListenerHandle, Error>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        let events = EventType::all_events();
+        let listener = self.new_listener(events[0].clone(), continue_after(callback));
+        let handle = listener.handle;
+        for event in events[1..].iter() {
+            let other = listener.for_event(event.clone());
+            self.add_listener(other);
+        }
+        self.add_listener(listener);
 
+        for event in events {
+            if let Err(e) = send_checked(self, subscribe_request(event)) {
+                self.listeners.retain(|l| !l.has_handle(handle));
+                return Err(e);
+            }
+        }
+        Ok(handle)
+    }
+
+    /// Subscribe to every event type and call the callback function every time any event occurs.
+    ///
+    /// Panics if the requests could not be sent, see `DaZeus::try_subscribe_all()` for a
+    /// non-panicking alternative.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// dazeus.subscribe_all(|evt, _| {
+    ///     println!("{} {:?}", evt.event, evt.params);
+    /// });
+    /// ```
+    pub fn subscribe_all<F>(&mut self, callback: F) -> ListenerHandle
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        let handle = self.current_handle;
+        unwrap_or_rejected(self.try_subscribe_all(callback), |_| handle)
+    }
+
+    /// Subscribe to an event type with a priority, and control whether the event is passed on.
+    ///
+    /// Listeners with a higher priority are called before listeners with a lower priority, and
+    /// listeners with the same priority are called in the order in which they were added. The
+    /// listeners added using the other subscribe methods have priority 0. If the callback returns
+    /// `Flow::Stop`, the event is not passed on to the listeners after it.
+    pub fn try_subscribe_with_priority<F>(
+        &mut self,
+        event: EventType,
+        priority: i32,
+        callback: F,
+    ) -> Result<(ListenerHandle, Response), Error>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) -> Flow + 'a,
+    {
+        let mut listener = self.new_listener(event, callback);
+        listener.priority = priority;
+        self.try_add_listener(listener)
+    }
+
+    /// Subscribe to an event type with a priority, and control whether the event is passed on.
+    ///
+    /// Panics if the request could not be sent, see `DaZeus::try_subscribe_with_priority()` for
+    /// a non-panicking alternative.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// dazeus.subscribe(EventType::PrivMsg, |evt, dazeus| {
+    ///     // count karma, but never for spam
+    /// });
+    ///
+    /// dazeus.subscribe_with_priority(EventType::PrivMsg, 10, |evt, dazeus| {
+    ///     if evt[3].contains("buy cheap") {
+    ///         dazeus.reply(&evt, "No spam please", true);
+    ///         Flow::Stop
+    ///     } else {
+    ///         Flow::Continue
+    ///     }
+    /// });
+    /// ```
+    pub fn subscribe_with_priority<F>(
+        &mut self,
+        event: EventType,
+        priority: i32,
+        callback: F,
+    ) -> (ListenerHandle, Response)
+    where
+        F: FnMut(Event, &dyn DaZeusClient) -> Flow + 'a,
+    {
+        unwrap_subscription(
+            self.current_handle,
+            self.try_subscribe_with_priority(event, priority, callback),
+        )
+    }
+
+    /// Change the priority of an existing listener.
+    ///
+    /// See `DaZeus::subscribe_with_priority()` for how priorities are used. Returns false if there
+    /// is no listener with the given handle.
+    pub fn set_priority(&mut self, handle: ListenerHandle, priority: i32) -> bool {
+        let mut found = false;
+        for listener in self.listeners.iter_mut().filter(|l| l.has_handle(handle)) {
+            listener.priority = priority;
+            found = true;
+        }
+        self.listeners.sort_by_key(|l| Reverse(l.priority));
+        found
+    }
+
+    /// Create a listener with a new handle.
+    fn new_listener<F>(&mut self, event: EventType, callback: F) -> Listener<'a>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) -> Flow + 'a,
+    {
         let handle = self.current_handle;
         self.current_handle += 1;
-        let listener = Listener::new(handle, event, callback);
+        Listener::new(handle, event, callback)
+    }
 
-        self.listeners.push(listener);
-        (handle, self.send(request))
+    /// Add a listener after the listeners with the same or a higher priority.
+    fn add_listener(&mut self, listener: Listener<'a>) {
+        let index = self
+            .listeners
+            .iter()
+            .position(|l| l.priority < listener.priority)
+            .unwrap_or(self.listeners.len());
+        self.listeners.insert(index, listener);
+    }
+
+    /// Add a listener and subscribe to its event type.
+    fn try_add_listener(
+        &mut self,
+        listener: Listener<'a>,
+    ) -> Result<(ListenerHandle, Response), Error> {
+        let handle = listener.handle;
+        let event = listener.event.clone();
+        self.add_listener(listener);
+
+        // don't keep a listener the caller got no handle for
+        let response = match send_checked(self, subscribe_request(event.clone())) {
+            Ok(response) => response,
+            Err(e) => {
+                self.listeners.retain(|l| !l.has_handle(handle));
+                return Err(e);
+            }
+        };
+
+        if let EventType::Command(ref command) = event {
+            self.commands.register_name(command);
+        }
+        Ok((handle, response))
+    }
+
+    /// Subscribe to a command and call the callback function every time such a command occurs.
+    pub fn try_subscribe_command<F>(
+        &mut self,
+        command: &str,
+        callback: F,
+    ) -> Result<(ListenerHandle, Response), Error>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        self.try_subscribe(EventType::Command(command.to_string()), callback)
     }
 
     /// Subscribe to a command and call the callback function every time such a command occurs.
+    ///
+    /// Panics if the request could not be sent, see `DaZeus::try_subscribe_command()` for a
+    /// non-panicking alternative.
     pub fn subscribe_command<F>(&mut self, command: &str, callback: F) -> (ListenerHandle, Response)
     where
         F: FnMut(Event, &dyn DaZeusClient) + 'a,
     {
-        self.subscribe(EventType::Command(command.to_string()), callback)
+        unwrap_subscription(
+            self.current_handle,
+            self.try_subscribe_command(command, callback),
+        )
+    }
+
+    /// Subscribe to a command and call the callback function with the parsed arguments every
+    /// time such a command occurs.
+    ///
+    /// The arguments are parsed according to the given `CommandSpec`. If they don't match, the
+    /// callback is not called and instead the user is replied to with what was wrong and the
+    /// usage of the command.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// let spec = CommandSpec::new("repeat")
+    ///     .arg("times", ArgType::Integer)
+    ///     .rest("message");
+    /// dazeus.subscribe_parsed_command(spec, |evt, args, dazeus| {
+    ///     for _ in 0..args.get_as::<u8>("times").unwrap() {
+    ///         dazeus.reply(&evt, args.get("message").unwrap(), false);
+    ///     }
+    /// });
+    /// ```
+    pub fn try_subscribe_parsed_command<F>(
+        &mut self,
+        spec: CommandSpec,
+        mut callback: F,
+    ) -> Result<(ListenerHandle, Response), Error>
+    where
+        F: FnMut(Event, CommandArgs, &dyn DaZeusClient) + 'a,
+    {
+        let command = spec.name().to_string();
+        self.commands.register(spec.clone());
+        self.try_subscribe_command(&command, move |evt, dazeus| {
+            let input = evt.params.get(4).map(|p| &p[..]).unwrap_or("");
+            match spec.parse(input) {
+                Ok(args) => callback(evt, args, dazeus),
+                Err(e) => {
+                    let msg = format!("{} (usage: {})", e.message(), spec.usage());
+                    if let Err(e) = dazeus.try_reply(&evt, &msg, true) {
+                        warn!("Could not reply with command usage: {}", e);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Subscribe to a command and call the callback function with the parsed arguments every
+    /// time such a command occurs.
+    ///
+    /// Panics if the request could not be sent, see `DaZeus::try_subscribe_parsed_command()` for
+    /// a non-panicking alternative.
+    pub fn subscribe_parsed_command<F>(
+        &mut self,
+        spec: CommandSpec,
+        callback: F,
+    ) -> (ListenerHandle, Response)
+    where
+        F: FnMut(Event, CommandArgs, &dyn DaZeusClient) + 'a,
+    {
+        unwrap_subscription(
+            self.current_handle,
+            self.try_subscribe_parsed_command(spec, callback),
+        )
+    }
+
+    /// Subscribe to a command that may only be used by users with some permission.
+    ///
+    /// Every time the command occurs, the permission is checked for the scope of the user in the
+    /// channel the command was given in. The `default` is used when no permission was set for
+    /// that scope. Only if the permission is granted the callback function is called, otherwise
+    /// the user is replied to with the message set by `DaZeus::set_permission_denied_reply()`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// dazeus.subscribe_command_with_permission("op", "channel.op", false, |evt, dazeus| {
+    ///     dazeus.reply(&evt, "You are the boss", true);
+    /// });
+    /// ```
+    pub fn try_subscribe_command_with_permission<F>(
+        &mut self,
+        command: &str,
+        permission: &str,
+        default: bool,
+        mut callback: F,
+    ) -> Result<(ListenerHandle, Response), Error>
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        let permission = permission.to_string();
+        let denied = self.permission_denied.clone();
+        self.try_subscribe_command(command, move |evt, dazeus| {
+            let scope = match targets_for_event(&evt) {
+                Some((network, channel, user)) => Scope::to(&network, &user, &channel),
+                None => return,
+            };
+
+            match dazeus.try_has_permission(&permission, default, scope) {
+                Ok(true) => callback(evt, dazeus),
+                Ok(false) => {
+                    if let Some(ref msg) = *denied.borrow() {
+                        if let Err(e) = dazeus.try_reply(&evt, msg, true) {
+                            warn!("Could not reply with permission denied: {}", e);
+                        }
+                    }
+                }
+                Err(e) => warn!("Could not check permission {}: {}", permission, e),
+            }
+        })
+    }
+
+    /// Subscribe to a command that may only be used by users with some permission.
+    ///
+    /// Panics if the request could not be sent, see
+    /// `DaZeus::try_subscribe_command_with_permission()` for a non-panicking alternative.
+    pub fn subscribe_command_with_permission<F>(
+        &mut self,
+        command: &str,
+        permission: &str,
+        default: bool,
+        callback: F,
+    ) -> (ListenerHandle, Response)
+    where
+        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+    {
+        unwrap_subscription(
+            self.current_handle,
+            self.try_subscribe_command_with_permission(command, permission, default, callback),
+        )
+    }
+
+    /// Set the reply that is sent when a user lacks the permission for a command.
+    ///
+    /// Use `None` to not reply at all. By default the reply is `Permission denied`.
+    pub fn set_permission_denied_reply(&mut self, reply: Option<&str>) {
+        *self.permission_denied.borrow_mut() = reply.map(|r| r.to_string());
+    }
+
+    /// Provide a description and usage of a command for the help command.
+    ///
+    /// Commands subscribed to using `DaZeus::subscribe_parsed_command()` are described by their
+    /// `CommandSpec` automatically. Use this method to describe commands that were subscribed to
+    /// in another way.
+    pub fn describe_command(&mut self, spec: CommandSpec) {
+        self.commands.register(spec);
+    }
+
+    /// Register a `help` command that lists the commands this plugin listens to.
+    ///
+    /// When given without arguments, the help command replies with the names of all commands.
+    /// When given the name of a command, it replies with the usage and description of that
+    /// command. Replies are sent as a notice, to avoid flooding channels.
+    pub fn try_enable_help(&mut self) -> Result<(ListenerHandle, Response), Error> {
+        let commands = self.commands.clone();
+        let spec = CommandSpec::new("help")
+            .help("Show the available commands, or how to use one of them")
+            .optional_arg("command", ArgType::Text);
+
+        self.try_subscribe_parsed_command(spec, move |evt, args, dazeus| {
+            let highlight = match dazeus.try_get_highlight_char() {
+                Ok(highlight) => highlight.unwrap_or_else(|| "".to_string()),
+                Err(e) => {
+                    warn!("Could not retrieve highlight character: {}", e);
+                    return;
+                }
+            };
+
+            let msg = match args.get("command") {
+                None => format!(
+                    "Available commands: {}. Use {}help <command> for more information.",
+                    commands.names().join(", "),
+                    highlight
+                ),
+                Some(name) => match commands.find(name.trim_start_matches(&highlight[..])) {
+                    Some(spec) => match spec.description() {
+                        Some(description) => {
+                            format!("{}{} - {}", highlight, spec.usage(), description)
+                        }
+                        None => format!("{}{}", highlight, spec.usage()),
+                    },
+                    None => format!("Unknown command: {}", name),
+                },
+            };
+
+            if let Err(e) = dazeus.try_reply_with_notice(&evt, &msg) {
+                warn!("Could not reply with help: {}", e);
+            }
+        })
+    }
+
+    /// Register a `help` command that lists the commands this plugin listens to.
+    ///
+    /// Panics if the request could not be sent, see `DaZeus::try_enable_help()` for a
+    /// non-panicking alternative.
+    pub fn enable_help(&mut self) -> (ListenerHandle, Response) {
+        unwrap_subscription(self.current_handle, self.try_enable_help())
     }
 }
 
 /// Methods for interaction with the DaZeus server.
+///
+/// Every method that communicates with the DaZeus core comes in two flavours: a `try_` method
+/// which returns any I/O or protocol error as an `Err`, and a method without the prefix that
+/// panics on such errors. When the core responds that it could not fulfill a request, the `try_`
+/// methods return an `Error::CoreRejected` containing the reason given by the core. The only
+/// exception is `DaZeusClient::try_send()`, which returns the response of the core as is. Requests
+/// that can't be fulfilled at all (such as replying to an event without a sender) are not sent,
+/// and result in an `Error::Refused`.
+///
+/// The methods without the prefix don't panic on rejected requests. Instead, they return the
+/// response indicating the failure, or nothing (`None`, an empty list or the given default) for
+/// methods that retrieve something. Methods that can't return either, such as
+/// `DaZeusClient::whois()`, do panic. Only the `try_` methods need to be implemented, the other
+/// variants are provided on top of them.
 pub trait DaZeusClient<'a> {
     /// Try to send a request to DaZeus
+    ///
+    /// Note that the response is returned as is, even when it indicates that the core could not
+    /// fulfill the request.
     fn try_send(&self, request: Request) -> Result<Response, Error>;
 
+    /// Try to unsubscribe a listener for some event.
+    fn try_unsubscribe(&mut self, handle: ListenerHandle) -> Result<Response, Error>;
+
+    /// Try to remove all subscriptions for a specific event type.
+    fn try_unsubscribe_all(&mut self, event: EventType) -> Result<Response, Error>;
+
+    /// Check if there is any active listener for the given event type.
+    fn has_any_subscription(&self, event: EventType) -> bool;
+
+    /// Stop `DaZeus::listen()` once the current event has been handled.
+    ///
+    /// See `StopHandle` for details.
+    fn stop(&self);
+
+    /// Try to retrieve the networks the bot is connected to.
+    fn try_networks(&self) -> Result<Vec<String>, Error> {
+        let resp = send_checked(self, Request::Networks)?;
+        Ok(resp.get_str_list("networks").unwrap_or_default())
+    }
+
+    /// Try to retrieve the channels the bot is in for a given network.
+    fn try_channels(&self, network: &str) -> Result<Vec<String>, Error> {
+        let resp = send_checked(self, Request::Channels(network.to_string()))?;
+        Ok(resp.get_str_list("channels").unwrap_or_default())
+    }
+
+    /// Try to send a message to a specific channel using the PRIVMSG method.
+    fn try_message(&self, network: &str, channel: &str, message: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Message(
+                network.to_string(),
+                channel.to_string(),
+                message.to_string(),
+            ),
+        )
+    }
+
+    /// Try to send a CTCP NOTICE to a specific channel.
+    fn try_notice(&self, network: &str, channel: &str, message: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Notice(
+                network.to_string(),
+                channel.to_string(),
+                message.to_string(),
+            ),
+        )
+    }
+
+    /// Try to send a CTCP REQUEST to a specific channel.
+    fn try_ctcp(&self, network: &str, channel: &str, message: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Ctcp(
+                network.to_string(),
+                channel.to_string(),
+                message.to_string(),
+            ),
+        )
+    }
+
+    /// Try to send a CTCP REPLY to a specific channel.
+    fn try_ctcp_reply(
+        &self,
+        network: &str,
+        channel: &str,
+        message: &str,
+    ) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::CtcpReply(
+                network.to_string(),
+                channel.to_string(),
+                message.to_string(),
+            ),
+        )
+    }
+
+    /// Try to send a CTCP ACTION to a specific channel
+    fn try_action(&self, network: &str, channel: &str, message: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Action(
+                network.to_string(),
+                channel.to_string(),
+                message.to_string(),
+            ),
+        )
+    }
+
+    /// Try to send a request for the list of nicks in a channel.
+    ///
+    /// See `DaZeusClient::send_names()` for details.
+    fn try_send_names(&self, network: &str, channel: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Names(network.to_string(), channel.to_string()),
+        )
+    }
+
+    /// Try to send a request for a whois of a specific nick on some network.
+    ///
+    /// See `DaZeusClient::send_whois()` for details.
+    fn try_send_whois(&self, network: &str, nick: &str) -> Result<Response, Error> {
+        send_checked(self, Request::Whois(network.to_string(), nick.to_string()))
+    }
+
+    /// Try to join a channel on some network.
+    fn try_join(&self, network: &str, channel: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Join(network.to_string(), channel.to_string()),
+        )
+    }
+
+    /// Try to leave a channel on some network.
+    fn try_part(&self, network: &str, channel: &str) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::Part(network.to_string(), channel.to_string()),
+        )
+    }
+
+    /// Try to retrieve the nickname of the bot on the given network.
+    fn try_nick(&self, network: &str) -> Result<Option<String>, Error> {
+        let resp = send_checked(self, Request::Nick(network.to_string()))?;
+        Ok(resp.get_str("nick").map(|s| s.to_string()))
+    }
+
+    /// Try to retrieve the nickname of the bot on the given network, without asking the core
+    /// every time.
+    ///
+    /// `DaZeus` only asks the core for the nick the first time, and keeps it up to date using
+    /// `NICK` events (subscribing to them if needed). Other clients always ask the core, as
+    /// `DaZeusClient::try_nick()` does.
+    fn try_cached_nick(&self, network: &str) -> Result<Option<String>, Error> {
+        self.try_nick(network)
+    }
+
+    /// Try to send a handshake to the DaZeus core.
+    fn try_handshake(
+        &self,
+        name: &str,
+        version: &str,
+        config: Option<&str>,
+    ) -> Result<Response, Error> {
+        let n = name.to_string();
+        let v = version.to_string();
+        let req = match config {
+            Some(config_name) => Request::Handshake(n, v, Some(config_name.to_string())),
+            None => Request::Handshake(n, v, None),
+        };
+        send_checked(self, req)
+    }
+
+    /// Try to retrieve a config value from the DaZeus config.
+    fn try_get_config(&self, name: &str, group: ConfigGroup) -> Result<Option<String>, Error> {
+        let resp = send_checked(self, Request::Config(name.to_string(), group))?;
+        Ok(resp.get_str("value").map(|s| s.to_string()))
+    }
+
+    /// Try to retrieve the character that is used by the bot for highlighting.
+    fn try_get_highlight_char(&self) -> Result<Option<String>, Error> {
+        self.try_get_config("highlight", ConfigGroup::Core)
+    }
+
+    /// Try to retrieve a property stored in the bot database.
+    ///
+    /// Returns `None` if the property was not set for the given scope.
+    fn try_get_property(&self, name: &str, scope: Scope) -> Result<Option<String>, Error> {
+        let resp = send_checked(self, Request::GetProperty(name.to_string(), scope))?;
+        Ok(resp.get_str("value").map(|s| s.to_string()))
+    }
+
+    /// Try to retrieve a property, falling back to less specific scopes if it was not set.
+    ///
+    /// The scopes are tried in the order given by `Scope::widen()`, so a property set for a user
+    /// in a channel overrides one set for the channel, which overrides one set for the network,
+    /// which overrides a global one. Returns the value along with the scope it was found for, or
+    /// `None` if the property was not set for any of the scopes.
+    fn try_get_property_cascading(
+        &self,
+        name: &str,
+        scope: Scope,
+    ) -> Result<Option<(String, Scope)>, Error> {
+        for scope in scope.widen() {
+            if let Some(value) = self.try_get_property(name, scope.clone())? {
+                return Ok(Some((value, scope)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Try to set a property to be stored in the bot database.
+    fn try_set_property(&self, name: &str, value: &str, scope: Scope) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::SetProperty(name.to_string(), value.to_string(), scope),
+        )
+    }
+
+    /// Try to remove a property stored in the bot database.
+    fn try_unset_property(&self, name: &str, scope: Scope) -> Result<Response, Error> {
+        send_checked(self, Request::UnsetProperty(name.to_string(), scope))
+    }
+
+    /// Try to retrieve a list of keys starting with the common prefix with the given scope.
+    fn try_get_property_keys(&self, prefix: &str, scope: Scope) -> Result<Vec<String>, Error> {
+        let resp = send_checked(self, Request::PropertyKeys(prefix.to_string(), scope))?;
+        Ok(resp.get_str_list("keys").unwrap_or_default())
+    }
+
+    /// Try to retrieve all properties starting with the common prefix with the given scope.
+    ///
+    /// The properties are returned as a Json object mapping the full name of every property to
+    /// its value. Note that every property is retrieved using a separate request.
+    fn try_export_properties(&self, prefix: &str, scope: Scope) -> Result<JsonValue, Error> {
+        let mut properties = JsonMap::new();
+        for key in self.try_get_property_keys(prefix, scope.clone())? {
+            if let Some(value) = self.try_get_property(&key, scope.clone())? {
+                properties.insert(key, JsonValue::String(value));
+            }
+        }
+        Ok(JsonValue::Object(properties))
+    }
+
+    /// Try to store all properties in a Json object with the given scope.
+    ///
+    /// The object should map property names to their values, as created by
+    /// `DaZeusClient::try_export_properties()`. Returns the number of stored properties. Nothing
+    /// is stored if the object contains a value that is not a string.
+    fn try_import_properties(&self, properties: &JsonValue, scope: Scope) -> Result<usize, Error> {
+        let properties = match properties.as_object() {
+            Some(obj) => obj,
+            None => return Err(InvalidJsonError::new("Properties are not an object").into()),
+        };
+        if properties.values().any(|v| !v.is_string()) {
+            return Err(InvalidJsonError::new("Property value is not a string").into());
+        }
+
+        for (name, value) in properties {
+            self.try_set_property(name, value.as_str().unwrap(), scope.clone())?;
+        }
+        Ok(properties.len())
+    }
+
+    /// Try to set a permission to either allow or deny for a specific scope.
+    fn try_set_permission(
+        &self,
+        permission: &str,
+        allow: bool,
+        scope: Scope,
+    ) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::SetPermission(permission.to_string(), allow, scope),
+        )
+    }
+
+    /// Try to retrieve whether for some scope the given permission was set.
+    ///
+    /// Will return the default if it was not.
+    fn try_has_permission(
+        &self,
+        permission: &str,
+        default: bool,
+        scope: Scope,
+    ) -> Result<bool, Error> {
+        let resp = send_checked(
+            self,
+            Request::HasPermission(permission.to_string(), default, scope),
+        )?;
+        Ok(resp.get_bool("has_permission").unwrap_or(default))
+    }
+
+    /// Try to remove a set permission from the bot.
+    fn try_unset_permission(&self, permission: &str, scope: Scope) -> Result<Response, Error> {
+        send_checked(
+            self,
+            Request::UnsetPermission(permission.to_string(), scope),
+        )
+    }
+
+    /// Try to send a whois request and wait for an event that answers this request (blocking).
+    ///
+    /// See `DaZeusClient::whois()` for details.
+    fn try_whois(&mut self, network: &str, nick: &str) -> Result<Event, Error>;
+
+    /// Try to send a names request and wait for an event that answers this request (blocking).
+    ///
+    /// See `DaZeusClient::names()` for details.
+    fn try_names(&mut self, network: &str, channel: &str) -> Result<Event, Error>;
+
+    /// Try to send a reply in response to some event.
+    ///
+    /// See `DaZeusClient::reply()` for details.
+    fn try_reply(&self, event: &Event, message: &str, highlight: bool) -> Result<Response, Error> {
+        if let Some((network, channel, user)) = targets_for_event(event) {
+            let nick = self.try_nick(&network)?.unwrap_or_else(|| "".to_string());
+            if channel == nick {
+                self.try_message(&network, &user, message)
+            } else if highlight {
+                let msg = format!("{}: {}", user, message);
+                self.try_message(&network, &channel, &msg[..])
+            } else {
+                self.try_message(&network, &channel, message)
+            }
+        } else {
+            Err(not_replyable(event))
+        }
+    }
+
+    /// Try to send a reply (as a notice) in response to some event.
+    ///
+    /// See `DaZeusClient::reply_with_notice()` for details.
+    fn try_reply_with_notice(&self, event: &Event, message: &str) -> Result<Response, Error> {
+        if let Some((network, channel, user)) = targets_for_event(event) {
+            let nick = self.try_nick(&network)?.unwrap_or_else(|| "".to_string());
+            if channel == nick {
+                self.try_notice(&network, &user, message)
+            } else {
+                self.try_notice(&network, &channel, message)
+            }
+        } else {
+            Err(not_replyable(event))
+        }
+    }
+
+    /// Try to send a reply (as a CTCP action) in response to some event.
+    ///
+    /// See `DaZeusClient::reply_with_action()` for details.
+    fn try_reply_with_action(&self, event: &Event, message: &str) -> Result<Response, Error> {
+        if let Some((network, channel, user)) = targets_for_event(event) {
+            let nick = self.try_nick(&network)?.unwrap_or_else(|| "".to_string());
+            if channel == nick {
+                self.try_action(&network, &user, message)
+            } else {
+                self.try_action(&network, &channel, message)
+            }
+        } else {
+            Err(not_replyable(event))
+        }
+    }
+
     /// Send a request to DaZeus and retrieve a Future in which the response will be contained.
-    fn send(&self, request: Request) -> Response;
+    fn send(&self, request: Request) -> Response {
+        unwrap_response(self.try_send(request))
+    }
 
     /// Unsubscribe a listener for some event.
-    fn unsubscribe(&mut self, handle: ListenerHandle) -> Response;
+    fn unsubscribe(&mut self, handle: ListenerHandle) -> Response {
+        unwrap_response(self.try_unsubscribe(handle))
+    }
 
     /// Remove all subscriptions for a specific event type.
-    fn unsubscribe_all(&mut self, event: EventType) -> Response;
-
-    /// Check if there is any active listener for the given event type.
-    fn has_any_subscription(&self, event: EventType) -> bool;
+    fn unsubscribe_all(&mut self, event: EventType) -> Response {
+        unwrap_response(self.try_unsubscribe_all(event))
+    }
 
     /// Retrieve the networks the bot is connected to.
-    fn networks(&self) -> Response;
+    fn networks(&self) -> Vec<String> {
+        unwrap_or_rejected(self.try_networks(), |_| Vec::new())
+    }
 
     /// Retrieve the channels the bot is in for a given network.
-    fn channels(&self, network: &str) -> Response;
+    fn channels(&self, network: &str) -> Vec<String> {
+        unwrap_or_rejected(self.try_channels(network), |_| Vec::new())
+    }
 
     /// Send a message to a specific channel using the PRIVMSG method.
-    fn message(&self, network: &str, channel: &str, message: &str) -> Response;
+    fn message(&self, network: &str, channel: &str, message: &str) -> Response {
+        unwrap_response(self.try_message(network, channel, message))
+    }
 
     /// Send a CTCP NOTICE to a specific channel.
-    fn notice(&self, network: &str, channel: &str, message: &str) -> Response;
+    fn notice(&self, network: &str, channel: &str, message: &str) -> Response {
+        unwrap_response(self.try_notice(network, channel, message))
+    }
 
     /// Send a CTCP REQUEST to a specific channel.
-    fn ctcp(&self, network: &str, channel: &str, message: &str) -> Response;
+    fn ctcp(&self, network: &str, channel: &str, message: &str) -> Response {
+        unwrap_response(self.try_ctcp(network, channel, message))
+    }
 
     /// Send a CTCP REPLY to a specific channel.
-    fn ctcp_reply(&self, network: &str, channel: &str, message: &str) -> Response;
+    fn ctcp_reply(&self, network: &str, channel: &str, message: &str) -> Response {
+        unwrap_response(self.try_ctcp_reply(network, channel, message))
+    }
 
     /// Send a CTCP ACTION to a specific channel
-    fn action(&self, network: &str, channel: &str, message: &str) -> Response;
+    fn action(&self, network: &str, channel: &str, message: &str) -> Response {
+        unwrap_response(self.try_action(network, channel, message))
+    }
 
     /// Send a request for the list of nicks in a channel.
     ///
@@ -175,7 +1781,9 @@ pub trait DaZeusClient<'a> {
     /// The Response will only indicate whether or not the request has been submitted successfully.
     /// The server may respond with an `EventType::Names` event any time after this request has
     /// been submitted.
-    fn send_names(&self, network: &str, channel: &str) -> Response;
+    fn send_names(&self, network: &str, channel: &str) -> Response {
+        unwrap_response(self.try_send_names(network, channel))
+    }
 
     /// Send a request for a whois of a specific nick on some network.
     ///
@@ -183,78 +1791,183 @@ pub trait DaZeusClient<'a> {
     /// The Response will only indicate whether or not the request has been submitted successfully.
     /// The server may respond with an `EventType::Whois` event any time after this request has
     /// been submitted.
-    fn send_whois(&self, network: &str, nick: &str) -> Response;
+    fn send_whois(&self, network: &str, nick: &str) -> Response {
+        unwrap_response(self.try_send_whois(network, nick))
+    }
 
     /// Try to join a channel on some network.
-    fn join(&self, network: &str, channel: &str) -> Response;
+    fn join(&self, network: &str, channel: &str) -> Response {
+        unwrap_response(self.try_join(network, channel))
+    }
 
     /// Try to leave a channel on some network.
-    fn part(&self, network: &str, channel: &str) -> Response;
+    fn part(&self, network: &str, channel: &str) -> Response {
+        unwrap_response(self.try_part(network, channel))
+    }
 
     /// Retrieve the nickname of the bot on the given network.
-    fn nick(&self, network: &str) -> Option<String>;
+    fn nick(&self, network: &str) -> Option<String> {
+        unwrap_or_rejected(self.try_nick(network), |_| None)
+    }
+
+    /// Retrieve the nickname of the bot on the given network, without asking the core every time.
+    ///
+    /// See `DaZeusClient::try_cached_nick()` for details.
+    fn cached_nick(&self, network: &str) -> Option<String> {
+        unwrap_or_rejected(self.try_cached_nick(network), |_| None)
+    }
 
     /// Send a handshake to the DaZeus core.
-    fn handshake(&self, name: &str, version: &str, config: Option<&str>) -> Response;
+    fn handshake(&self, name: &str, version: &str, config: Option<&str>) -> Response {
+        unwrap_response(self.try_handshake(name, version, config))
+    }
 
     /// Retrieve a config value from the DaZeus config.
-    fn get_config(&self, name: &str, group: ConfigGroup) -> Response;
+    fn get_config(&self, name: &str, group: ConfigGroup) -> Option<String> {
+        unwrap_or_rejected(self.try_get_config(name, group), |_| None)
+    }
 
     /// Retrieve the character that is used by the bot for highlighting.
-    fn get_highlight_char(&self) -> Option<String>;
+    fn get_highlight_char(&self) -> Option<String> {
+        unwrap_or_rejected(self.try_get_highlight_char(), |_| None)
+    }
 
     /// Retrieve a property stored in the bot database.
-    fn get_property(&self, name: &str, scope: Scope) -> Response;
+    ///
+    /// Returns `None` if the property was not set for the given scope.
+    fn get_property(&self, name: &str, scope: Scope) -> Option<String> {
+        unwrap_or_rejected(self.try_get_property(name, scope), |_| None)
+    }
+
+    /// Retrieve a property, falling back to less specific scopes if it was not set.
+    ///
+    /// Returns the value along with the scope it was found for. See
+    /// `DaZeusClient::try_get_property_cascading()` for the order in which scopes are tried.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// dazeus.subscribe(EventType::PrivMsg, |evt, dazeus| {
+    ///     let scope = Scope::from_event(&evt).unwrap();
+    ///     if let Some((greeting, _)) = dazeus.get_property_cascading("greeting", scope) {
+    ///         dazeus.reply(&evt, &greeting, true);
+    ///     }
+    /// });
+    /// ```
+    fn get_property_cascading(&self, name: &str, scope: Scope) -> Option<(String, Scope)> {
+        unwrap_or_rejected(self.try_get_property_cascading(name, scope), |_| None)
+    }
 
     /// Set a property to be stored in the bot database.
-    fn set_property(&self, name: &str, value: &str, scope: Scope) -> Response;
+    fn set_property(&self, name: &str, value: &str, scope: Scope) -> Response {
+        unwrap_response(self.try_set_property(name, value, scope))
+    }
 
     /// Remove a property stored in the bot database.
-    fn unset_property(&self, name: &str, scope: Scope) -> Response;
+    fn unset_property(&self, name: &str, scope: Scope) -> Response {
+        unwrap_response(self.try_unset_property(name, scope))
+    }
 
     /// Retrieve a list of keys starting with the common prefix with the given scope.
-    fn get_property_keys(&self, prefix: &str, scope: Scope) -> Response;
+    fn get_property_keys(&self, prefix: &str, scope: Scope) -> Vec<String> {
+        unwrap_or_rejected(self.try_get_property_keys(prefix, scope), |_| Vec::new())
+    }
+
+    /// Retrieve all properties starting with the common prefix with the given scope.
+    ///
+    /// The properties are returned as a Json object mapping the full name of every property to
+    /// its value, which can be stored using `DaZeusClient::import_properties()`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use dazeus::*;
+    /// # use std::str::FromStr;
+    /// # use std::fs;
+    /// # let dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+    /// let backup = dazeus.export_properties("quotes.", Scope::any());
+    /// fs::write("quotes.json", backup.to_string()).unwrap();
+    ///
+    /// // and later, possibly on another core
+    /// let backup = serde_json::from_str(&fs::read_to_string("quotes.json").unwrap()).unwrap();
+    /// dazeus.import_properties(&backup, Scope::any());
+    /// ```
+    fn export_properties(&self, prefix: &str, scope: Scope) -> JsonValue {
+        unwrap_or_rejected(self.try_export_properties(prefix, scope), |_| {
+            JsonValue::Object(JsonMap::new())
+        })
+    }
+
+    /// Store all properties in a Json object with the given scope.
+    ///
+    /// Returns the number of stored properties. Panics if the properties could not be stored,
+    /// including when DaZeus rejected one of them, see `DaZeusClient::try_import_properties()` for
+    /// a non-panicking alternative.
+    fn import_properties(&self, properties: &JsonValue, scope: Scope) -> usize {
+        unwrap_or_panic(self.try_import_properties(properties, scope))
+    }
 
     /// Set a permission to either allow or deny for a specific scope.
-    fn set_permission(&self, permission: &str, allow: bool, scope: Scope) -> Response;
+    fn set_permission(&self, permission: &str, allow: bool, scope: Scope) -> Response {
+        unwrap_response(self.try_set_permission(permission, allow, scope))
+    }
 
     /// Retrieve whether for some scope the given permission was set.
     ///
     /// Will return the default if it was not.
-    fn has_permission(&self, permission: &str, default: bool, scope: Scope) -> Response;
+    fn has_permission(&self, permission: &str, default: bool, scope: Scope) -> bool {
+        unwrap_or_rejected(self.try_has_permission(permission, default, scope), |_| {
+            default
+        })
+    }
 
     /// Remove a set permission from the bot.
-    fn unset_permission(&self, permission: &str, scope: Scope) -> Response;
+    fn unset_permission(&self, permission: &str, scope: Scope) -> Response {
+        unwrap_response(self.try_unset_permission(permission, scope))
+    }
 
     /// Send a whois request and wait for an event that answers this request (blocking).
     ///
     /// Note that the IRC server may not respond to the whois request (if it has been configured
-    /// this way), in which case this request will block forever.
-    fn whois(&mut self, network: &str, nick: &str) -> Event;
+    /// this way), in which case this request will panic once the timeout set using
+    /// `DaZeus::set_event_timeout()` has passed. It also panics when DaZeus rejected the request.
+    fn whois(&mut self, network: &str, nick: &str) -> Event {
+        unwrap_or_panic(self.try_whois(network, nick))
+    }
 
     /// Send a names request and wait for an event that answers this request (blocking).
     ///
     /// Note that the IRC server may not respond to the names request (if it has been configured
-    /// this way), in which case this request will block forever.
-    fn names(&mut self, network: &str, channel: &str) -> Event;
+    /// this way), in which case this request will panic once the timeout set using
+    /// `DaZeus::set_event_timeout()` has passed. It also panics when DaZeus rejected the request.
+    fn names(&mut self, network: &str, channel: &str) -> Event {
+        unwrap_or_panic(self.try_names(network, channel))
+    }
 
     /// Send a reply in response to some event.
     ///
     /// Note that not all types of events can be responded to. Mostly message type events
     /// concerning some IRC user can be responded to. Join events can also be responded to.
-    fn reply(&self, event: &Event, message: &str, highlight: bool) -> Response;
+    fn reply(&self, event: &Event, message: &str, highlight: bool) -> Response {
+        unwrap_response(self.try_reply(event, message, highlight))
+    }
 
     /// Send a reply (as a notice) in response to some event.
     ///
     /// Note that not all types of events can be responded to. Mostly message type events
     /// concerning some IRC user can be responded to. Join events can also be responded to.
-    fn reply_with_notice(&self, event: &Event, message: &str) -> Response;
+    fn reply_with_notice(&self, event: &Event, message: &str) -> Response {
+        unwrap_response(self.try_reply_with_notice(event, message))
+    }
 
-    /// Send a reply (as a ctcp action) in response to some event.
+    /// Send a reply (as a CTCP action) in response to some event.
     ///
     /// Note that not all types of events can be responded to. Mostly message type events
     /// concerning some IRC user can be responded to. Join events can also be responded to.
-    fn reply_with_action(&self, event: &Event, message: &str) -> Response;
+    fn reply_with_action(&self, event: &Event, message: &str) -> Response {
+        unwrap_response(self.try_reply_with_action(event, message))
+    }
 }
 
 impl<'a, T> DaZeusClient<'a> for DaZeus<'a, T>
@@ -263,52 +1976,56 @@ where
 {
     /// Try to send a request to DaZeus
     fn try_send(&self, request: Request) -> Result<Response, Error> {
-        self.handler.borrow_mut().write(request)?;
-        self.next_response()
-    }
-
-    /// Send a request to DaZeus and retrieve a Future in which the response will be contained.
-    fn send(&self, request: Request) -> Response {
-        match self.try_send(request) {
-            Ok(response) => response,
-            Err(e) => panic!("{}", e),
+        let request = self.limiter.borrow_mut().submit(request);
+        match request {
+            Some(request) => self.send_now(request),
+            None => Ok(Response::for_queued()),
         }
     }
 
-    /// Unsubscribe a listener for some event.
-    fn unsubscribe(&mut self, handle: ListenerHandle) -> Response {
-        // first find the event type
-        let event = {
-            match self.listeners.iter().find(|l| l.has_handle(handle)) {
-                Some(listener) => Some(listener.event.clone()),
-                None => None,
-            }
-        };
+    /// Try to unsubscribe a listener for some event.
+    fn try_unsubscribe(&mut self, handle: ListenerHandle) -> Result<Response, Error> {
+        // first find the event types (there may be several for `DaZeus::subscribe_all()`)
+        let events: Vec<EventType> = self
+            .listeners
+            .iter()
+            .filter(|l| l.has_handle(handle))
+            .map(|listener| listener.event.clone())
+            .collect();
+
+        if events.is_empty() {
+            return Err(Error::Refused {
+                request: format!("Unsubscribe listener {}", handle),
+                reason: "Could not find listener with given handle".to_string(),
+            });
+        }
 
         self.listeners.retain(|l| !l.has_handle(handle));
-        match event {
-            // we can't unsubscribe commands
-            Some(EventType::Command(_)) => Response::for_success(),
-
-            // unsubscribe if there are no more listeners for the event
-            Some(evt) => {
-                if self.listeners.iter().any(|l| l.event == evt) {
-                    Response::for_success()
-                } else {
-                    self.send(Request::Unsubscribe(evt))
+        let mut response = Response::for_success();
+        for evt in events {
+            match evt {
+                // we can't unsubscribe commands
+                EventType::Command(_) => (),
+
+                // unsubscribe if there are no more listeners for the event
+                evt => {
+                    if !self.is_subscribed(&evt) {
+                        response = send_checked(self, Request::Unsubscribe(evt))?;
+                    }
                 }
             }
-
-            None => Response::for_fail("Could not find listener with given handle"),
         }
+        Ok(response)
     }
 
-    /// Remove all subscriptions for a specific event type.
-    fn unsubscribe_all(&mut self, event: EventType) -> Response {
+    /// Try to remove all subscriptions for a specific event type.
+    fn try_unsubscribe_all(&mut self, event: EventType) -> Result<Response, Error> {
         self.listeners.retain(|l| l.event != event);
         match event {
-            EventType::Command(_) => Response::for_success(),
-            _ => self.send(Request::Unsubscribe(event)),
+            EventType::Command(_) => Ok(Response::for_success()),
+            // the nick of the bot is still being kept track of
+            _ if self.is_subscribed(&event) => Ok(Response::for_success()),
+            _ => send_checked(self, Request::Unsubscribe(event)),
         }
     }
 
@@ -317,282 +2034,815 @@ where
         self.listeners.iter().any(|l| l.event == event)
     }
 
-    /// Retrieve the networks the bot is connected to.
-    fn networks(&self) -> Response {
-        self.send(Request::Networks)
-    }
+    /// Try to retrieve the nickname of the bot on the given network, without asking the core
+    /// every time.
+    fn try_cached_nick(&self, network: &str) -> Result<Option<String>, Error> {
+        if let Some(nick) = self.nicks.borrow().get(network) {
+            return Ok(Some(nick.clone()));
+        }
 
-    /// Retrieve the channels the bot is in for a given network.
-    fn channels(&self, network: &str) -> Response {
-        self.send(Request::Channels(network.to_string()))
+        if !self.is_subscribed(&EventType::Nick) {
+            send_checked(self, Request::Subscribe(EventType::Nick))?;
+        }
+        self.tracking_nicks.set(true);
+        let nick = self.try_nick(network)?;
+        if let Some(ref nick) = nick {
+            self.nicks
+                .borrow_mut()
+                .insert(network.to_string(), nick.clone());
+        }
+        Ok(nick)
     }
 
-    /// Send a message to a specific channel using the PRIVMSG method.
-    fn message(&self, network: &str, channel: &str, message: &str) -> Response {
-        self.send(Request::Message(
-            network.to_string(),
-            channel.to_string(),
-            message.to_string(),
-        ))
+    /// Stop `DaZeus::listen()` once the current event has been handled.
+    fn stop(&self) {
+        self.stop.stop();
     }
 
-    /// Send a CTCP NOTICE to a specific channel.
-    fn notice(&self, network: &str, channel: &str, message: &str) -> Response {
-        self.send(Request::Notice(
-            network.to_string(),
-            channel.to_string(),
-            message.to_string(),
-        ))
+    /// Try to send a whois request and wait for an event that answers this request (blocking).
+    fn try_whois(&mut self, network: &str, nick: &str) -> Result<Event, Error> {
+        self.request_event(
+            Some(Request::Whois(network.to_string(), nick.to_string())),
+            EventType::Whois,
+            |evt| answers_whois(evt, network, nick),
+            self.event_timeout,
+        )
     }
 
-    /// Send a CTCP REQUEST to a specific channel.
-    fn ctcp(&self, network: &str, channel: &str, message: &str) -> Response {
-        self.send(Request::Ctcp(
-            network.to_string(),
-            channel.to_string(),
-            message.to_string(),
-        ))
+    /// Try to send a names request and wait for an event that answers this request (blocking).
+    fn try_names(&mut self, network: &str, channel: &str) -> Result<Event, Error> {
+        self.request_event(
+            Some(Request::Names(network.to_string(), channel.to_string())),
+            EventType::Names,
+            |evt| answers_names(evt, network, channel),
+            self.event_timeout,
+        )
     }
+}
 
-    /// Send a CTCP REPLY to a specific channel.
-    fn ctcp_reply(&self, network: &str, channel: &str, message: &str) -> Response {
-        self.send(Request::CtcpReply(
-            network.to_string(),
-            channel.to_string(),
-            message.to_string(),
-        ))
-    }
+/// Send a request and turn a response indicating failure into an `Error::CoreRejected`.
+fn send_checked<'a, C>(client: &C, request: Request) -> Result<Response, Error>
+where
+    C: DaZeusClient<'a> + ?Sized,
+{
+    let description = format!("{:?}", request);
+    check_response(description, client.try_send(request)?)
+}
 
-    /// Send a CTCP ACTION to a specific channel
-    fn action(&self, network: &str, channel: &str, message: &str) -> Response {
-        self.send(Request::Action(
-            network.to_string(),
-            channel.to_string(),
-            message.to_string(),
-        ))
+/// Turn a response indicating failure into an `Error::CoreRejected` for the described request.
+pub(crate) fn check_response(request: String, response: Response) -> Result<Response, Error> {
+    if response.has_success() {
+        Ok(response)
+    } else {
+        Err(Error::CoreRejected {
+            request,
+            reason: response.get_str_or("reason", "").to_string(),
+            response,
+        })
     }
+}
 
-    /// Send a request for the list of nicks in a channel.
-    ///
-    /// Note that the response will not contain the names data, instead listen for a names event.
-    /// The Response will only indicate whether or not the request has been submitted successfully.
-    /// The server may respond with an `EventType::Names` event any time after this request has
-    /// been submitted.
-    fn send_names(&self, network: &str, channel: &str) -> Response {
-        self.send(Request::Names(network.to_string(), channel.to_string()))
+/// Wrap a callback that does not control the flow of events, so that it passes events on.
+fn continue_after<'a, F>(mut callback: F) -> impl FnMut(Event, &dyn DaZeusClient) -> Flow + 'a
+where
+    F: FnMut(Event, &dyn DaZeusClient) + 'a,
+{
+    move |evt, dazeus| {
+        callback(evt, dazeus);
+        Flow::Continue
     }
+}
 
-    /// Send a request for a whois of a specific nick on some network.
-    ///
-    /// Note that the response will not contain the whois data, instead listen for a whois event.
-    /// The Response will only indicate whether or not the request has been submitted successfully.
-    /// The server may respond with an `EventType::Whois` event any time after this request has
-    /// been submitted.
-    fn send_whois(&self, network: &str, nick: &str) -> Response {
-        self.send(Request::Whois(network.to_string(), nick.to_string()))
+/// Whether the event is the answer to a whois request for the nick on the network.
+fn answers_whois(event: &Event, network: &str, nick: &str) -> bool {
+    match event.as_typed() {
+        Ok(TypedEvent::Whois(whois)) => whois.network == network && whois.nick == nick,
+        _ => false,
     }
+}
 
-    /// Try to join a channel on some network.
-    fn join(&self, network: &str, channel: &str) -> Response {
-        self.send(Request::Join(network.to_string(), channel.to_string()))
+/// Whether the event is the answer to a names request for the channel on the network.
+fn answers_names(event: &Event, network: &str, channel: &str) -> bool {
+    match event.as_typed() {
+        Ok(TypedEvent::Names(names)) => names.network == network && names.channel == channel,
+        _ => false,
     }
+}
 
-    /// Try to leave a channel on some network.
-    fn part(&self, network: &str, channel: &str) -> Response {
-        self.send(Request::Part(network.to_string(), channel.to_string()))
+/// Construct the request for subscribing to the given event type.
+pub(crate) fn subscribe_request(event: EventType) -> Request {
+    match event {
+        EventType::Command(cmd) => Request::SubscribeCommand(cmd, None),
+        _ => Request::Subscribe(event),
     }
+}
 
-    /// Retrieve the nickname of the bot on the given network.
-    fn nick(&self, network: &str) -> Option<String> {
-        let resp = self.send(Request::Nick(network.to_string()));
-        match resp.get_str("nick") {
-            Some(s) => Some(s.to_string()),
-            None => None,
-        }
+/// Unwrap the result of a `try_` method, panicking with the error message on failure.
+pub(crate) fn unwrap_or_panic<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("{}", e),
     }
+}
 
-    /// Send a handshake to the DaZeus core.
-    fn handshake(&self, name: &str, version: &str, config: Option<&str>) -> Response {
-        let n = name.to_string();
-        let v = version.to_string();
-        let req = match config {
-            Some(config_name) => Request::Handshake(n, v, Some(config_name.to_string())),
-            None => Request::Handshake(n, v, None),
-        };
-        self.send(req)
+/// Unwrap the result of a `try_` method, turning a rejected request into a value using the
+/// response indicating the failure, and panicking with the error message on any other failure.
+pub(crate) fn unwrap_or_rejected<T, F>(result: Result<T, Error>, rejected: F) -> T
+where
+    F: FnOnce(Response) -> T,
+{
+    match result {
+        Ok(value) => value,
+        Err(Error::CoreRejected { response, .. }) => rejected(response),
+        Err(Error::Refused { reason, .. }) => rejected(Response::for_fail(&reason)),
+        Err(e) => panic!("{}", e),
     }
+}
 
-    /// Retrieve a config value from the DaZeus config.
-    fn get_config(&self, name: &str, group: ConfigGroup) -> Response {
-        self.send(Request::Config(name.to_string(), group))
+/// Unwrap the result of a `try_` method, returning the response indicating failure when the
+/// request was rejected, and panicking with the error message on any other failure.
+pub(crate) fn unwrap_response(result: Result<Response, Error>) -> Response {
+    unwrap_or_rejected(result, |response| response)
+}
+
+/// Unwrap the result of subscribing the listener with the given handle, returning the response
+/// indicating failure when the subscription was rejected.
+fn unwrap_subscription(
+    handle: ListenerHandle,
+    result: Result<(ListenerHandle, Response), Error>,
+) -> (ListenerHandle, Response) {
+    unwrap_or_rejected(result, |response| (handle, response))
+}
+
+/// The error for a reply to an event that was not sent by anyone.
+fn not_replyable(event: &Event) -> Error {
+    Error::Refused {
+        request: format!("Reply to {}", event.event),
+        reason: "Not an event to reply to".to_string(),
     }
+}
 
-    /// Retrieve the character that is used by the bot for highlighting.
-    fn get_highlight_char(&self) -> Option<String> {
-        let resp = self.get_config("highlight", ConfigGroup::Core);
-        match resp.get_str("value") {
-            Some(s) => Some(s.to_string()),
-            None => None,
-        }
+fn targets_for_event(event: &Event) -> Option<(String, String, String)> {
+    match event.as_typed() {
+        Ok(TypedEvent::Join(e)) => Some((e.network, e.channel, e.nick)),
+        Ok(TypedEvent::PrivMsg(e))
+        | Ok(TypedEvent::Notice(e))
+        | Ok(TypedEvent::Ctcp(e))
+        | Ok(TypedEvent::Action(e)) => Some((e.network, e.channel, e.sender)),
+        Ok(TypedEvent::Command(e)) => Some((e.network, e.channel, e.sender)),
+        _ => None,
     }
+}
 
-    /// Retrieve a property stored in the bot database.
-    fn get_property(&self, name: &str, scope: Scope) -> Response {
-        self.send(Request::GetProperty(name.to_string(), scope))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{event, privmsg, response, MockConnection, MockCore};
+    use serde_json::json;
+
+    fn nick_response(nick: &str) -> Response {
+        response(json!({"success": true, "nick": nick}))
     }
 
-    /// Set a property to be stored in the bot database.
-    fn set_property(&self, name: &str, value: &str, scope: Scope) -> Response {
-        self.send(Request::SetProperty(
-            name.to_string(),
-            value.to_string(),
-            scope,
-        ))
+    fn message(text: &str) -> Event {
+        privmsg("local", "someone", "#channel", text)
     }
 
-    /// Remove a property stored in the bot database.
-    fn unset_property(&self, name: &str, scope: Scope) -> Response {
-        self.send(Request::UnsetProperty(name.to_string(), scope))
+    #[test]
+    fn response_queue_is_fifo() {
+        let mut queue = ResponseQueue::new();
+        let first = queue.issue();
+        let second = queue.issue();
+        queue.receive(nick_response("first")).unwrap();
+        queue.receive(nick_response("second")).unwrap();
+        assert!(queue.receive(Response::for_success()).is_err());
+
+        assert_eq!(queue.take(second), Some(nick_response("second")));
+        assert_eq!(queue.take(first), Some(nick_response("first")));
+        assert_eq!(queue.take(first), None);
     }
 
-    /// Retrieve a list of keys starting with the common prefix with the given scope.
-    fn get_property_keys(&self, prefix: &str, scope: Scope) -> Response {
-        self.send(Request::PropertyKeys(prefix.to_string(), scope))
+    #[test]
+    fn abandoned_response_is_dropped() {
+        let mut queue = ResponseQueue::new();
+        let first = queue.issue();
+        let second = queue.issue();
+        queue.abandon(first);
+        queue.receive(nick_response("first")).unwrap();
+        queue.receive(nick_response("second")).unwrap();
+
+        assert_eq!(queue.take(first), None);
+        assert_eq!(queue.take(second), Some(nick_response("second")));
+        assert!(queue.received.is_empty() && queue.abandoned.is_empty());
     }
 
-    /// Set a permission to either allow or deny for a specific scope.
-    fn set_permission(&self, permission: &str, allow: bool, scope: Scope) -> Response {
-        self.send(Request::SetPermission(permission.to_string(), allow, scope))
+    #[test]
+    fn late_response_after_timeout() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.set_request_timeout(Duration::from_millis(50));
+
+        core.time_out_reads(usize::MAX);
+        match dazeus.try_networks() {
+            Err(Error::Timeout(_)) => (),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+
+        // the response to the networks request arrives after the event is injected
+        core.time_out_reads(0);
+        core.inject_event(message("hello"));
+        assert_eq!(dazeus.try_next_event().unwrap(), message("hello"));
+        assert!(dazeus.queue.borrow().received.is_empty());
+        assert!(dazeus.queue.borrow().pending.is_empty());
+
+        core.respond(nick_response("DaZeus"));
+        assert_eq!(
+            dazeus.try_nick("local").unwrap(),
+            Some("DaZeus".to_string())
+        );
     }
 
-    /// Retrieve whether for some scope the given permission was set.
-    ///
-    /// Will return the default if it was not.
-    fn has_permission(&self, permission: &str, default: bool, scope: Scope) -> Response {
-        self.send(Request::HasPermission(
-            permission.to_string(),
-            default,
-            scope,
-        ))
+    #[test]
+    fn slow_response_within_request_timeout() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+
+        // the read timeout of the connection passes several times before the core responds
+        core.respond(nick_response("DaZeus"));
+        core.time_out_reads(5);
+        assert_eq!(
+            dazeus.try_nick("local").unwrap(),
+            Some("DaZeus".to_string())
+        );
     }
 
-    /// Remove a set permission from the bot.
-    fn unset_permission(&self, permission: &str, scope: Scope) -> Response {
-        self.send(Request::UnsetPermission(permission.to_string(), scope))
+    #[test]
+    fn nested_request_gets_its_own_response() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.subscribe(EventType::PrivMsg, |_, _| ());
+
+        let inner = Rc::new(RefCell::new(None));
+        let result = inner.clone();
+        dazeus.subscribe(EventType::PrivMsg, move |evt, dazeus| {
+            *result.borrow_mut() = Some(dazeus.try_nick(&evt[3]).unwrap());
+        });
+
+        // the event arrives while the outer request is waiting for its response
+        core.inject_event(message("inner"));
+        core.respond(nick_response("Outer"));
+        core.respond(nick_response("Inner"));
+
+        assert_eq!(dazeus.try_nick("outer").unwrap(), Some("Outer".to_string()));
+        assert_eq!(*inner.borrow(), Some(Some("Inner".to_string())));
+        assert_eq!(
+            core.requests()[2..],
+            [
+                Request::Nick("outer".to_string()),
+                Request::Nick("inner".to_string())
+            ]
+        );
     }
 
-    /// Send a whois request and wait for an event that answers this request (blocking).
-    ///
-    /// Note that the IRC server may not respond to the whois request (if it has been configured
-    /// this way), in which case this request will block forever.
-    fn whois(&mut self, network: &str, nick: &str) -> Event {
-        if !self.has_any_subscription(EventType::Whois) {
-            self.send(Request::Subscribe(EventType::Whois));
-        }
-        self.send_whois(network, nick);
+    #[test]
+    fn rejected_subscription_removes_listener() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        core.respond(Response::for_fail("Not allowed"));
 
-        loop {
-            let evt = self.next_event();
-            match evt.event {
-                EventType::Whois if &evt[0] == network && &evt[2] == nick => {
-                    if !self.has_any_subscription(EventType::Whois) {
-                        self.send(Request::Unsubscribe(EventType::Whois));
-                    }
-                    return evt;
-                }
-                _ => (),
-            }
+        match dazeus.try_subscribe(EventType::PrivMsg, |_, _| ()) {
+            Err(Error::CoreRejected { .. }) => (),
+            other => panic!("Expected a rejection, got {:?}", other),
         }
+        assert!(!dazeus.has_any_subscription(EventType::PrivMsg));
     }
 
-    /// Send a names request and wait for an event that answers this request (blocking).
-    ///
-    /// Note that the IRC server may not respond to the names request (if it has been configured
-    /// this way), in which case this request will block forever.
-    fn names(&mut self, network: &str, channel: &str) -> Event {
-        if !self.has_any_subscription(EventType::Names) {
-            self.send(Request::Subscribe(EventType::Names));
-        }
-        self.send_names(network, channel);
+    #[test]
+    fn rejected_subscribe_all_removes_listeners() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        core.respond(Response::for_success());
+        core.respond(Response::for_fail("Not allowed"));
 
-        loop {
-            let evt = self.next_event();
-            match evt.event {
-                EventType::Names if &evt[0] == network && &evt[2] == channel => {
-                    if !self.has_any_subscription(EventType::Names) {
-                        self.send(Request::Unsubscribe(EventType::Names));
-                    }
-                    return evt;
-                }
-                _ => (),
-            }
+        assert!(dazeus.try_subscribe_all(|_, _| ()).is_err());
+        for event in EventType::all_events() {
+            assert!(!dazeus.has_any_subscription(event));
         }
     }
 
-    /// Send a reply in response to some event.
-    ///
-    /// Note that not all types of events can be responded to. Mostly message type events
-    /// concerning some IRC user can be responded to. Join events can also be responded to.
-    fn reply(&self, event: &Event, message: &str, highlight: bool) -> Response {
-        if let Some((network, channel, user)) = targets_for_event(event) {
-            let nick = self.nick(network).unwrap_or_else(|| "".to_string());
-            if channel == nick {
-                self.message(network, user, message)
-            } else if highlight {
-                let msg = format!("{}: {}", user, message);
-                self.message(network, channel, &msg[..])
-            } else {
-                self.message(network, channel, message)
+    #[test]
+    fn rejected_request_returns_response() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        core.respond(response(
+            json!({"success": false, "reason": "No such network"}),
+        ));
+        core.respond(Response::for_fail("Not allowed"));
+
+        let response = dazeus.join("nonet", "#a");
+        assert_eq!(response.get_str("reason"), Some("No such network"));
+        let (handle, response) = dazeus.subscribe(EventType::PrivMsg, |_, _| ());
+        assert!(!response.has_success());
+        assert!(!dazeus.has_any_subscription(EventType::PrivMsg));
+        assert!(!dazeus.unsubscribe(handle).has_success());
+    }
+
+    #[test]
+    fn whois_skips_malformed_events() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        core.respond(Response::for_success());
+        core.respond_with_events(
+            Response::for_success(),
+            vec![
+                event(EventType::Whois, &["local"]),
+                event(EventType::Whois, &["local", "server", "someone"]),
+            ],
+        );
+
+        let whois = dazeus.try_whois("local", "someone").unwrap();
+        assert_eq!(whois.params.len(), 3);
+    }
+
+    #[test]
+    fn cached_nick_follows_nick_events() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        core.respond(Response::for_success());
+        core.respond(nick_response("DaZeus"));
+        assert_eq!(dazeus.cached_nick("local"), Some("DaZeus".to_string()));
+        assert_eq!(dazeus.cached_nick("local"), Some("DaZeus".to_string()));
+        assert_eq!(
+            core.requests(),
+            [
+                Request::Subscribe(EventType::Nick),
+                Request::Nick("local".to_string())
+            ]
+        );
+
+        core.clear_requests();
+        core.inject_event(event(EventType::Nick, &["local", "someone", "other"]));
+        core.inject_event(event(EventType::Nick, &["local", "DaZeus", "DaZeus_"]));
+        dazeus.try_next_event().unwrap();
+        dazeus.try_next_event().unwrap();
+        assert_eq!(dazeus.cached_nick("local"), Some("DaZeus_".to_string()));
+
+        // the core keeps sending nick events after the last listener for them is removed
+        let (handle, _) = dazeus.subscribe(EventType::Nick, |_, _| ());
+        dazeus.unsubscribe(handle);
+        assert_eq!(core.requests(), [Request::Subscribe(EventType::Nick)]);
+    }
+
+    #[test]
+    fn reconnect_restores_handshake_and_subscriptions() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.handshake("plugin", "1", None);
+        dazeus.subscribe(EventType::PrivMsg, |_, _| ());
+        dazeus.subscribe(EventType::PrivMsg, |_, _| ());
+        dazeus.subscribe_command("hello", |_, _| ());
+        let handshake = core.requests()[0].clone();
+
+        // the first attempt hands out a new connection, which is closed right away as well
+        let cores = std::rc::Rc::new(RefCell::new(Vec::new()));
+        let attempts = std::rc::Rc::new(Cell::new(0));
+        let (connected, counter) = (cores.clone(), attempts.clone());
+        let policy = ReconnectPolicy::new(Duration::from_millis(1), Duration::from_millis(1))
+            .with_max_attempts(3);
+        dazeus.set_reconnect(policy, move || {
+            counter.set(counter.get() + 1);
+            if counter.get() > 1 {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
             }
-        } else {
-            Response::for_fail("Not an event to reply to")
+            let (core, conn) = MockCore::new();
+            connected.borrow_mut().push(core);
+            Ok(conn)
+        });
+
+        assert!(dazeus.listen().is_err());
+        assert_eq!(attempts.get(), 4);
+        assert_eq!(
+            cores.borrow()[0].requests(),
+            [
+                handshake,
+                Request::Subscribe(EventType::PrivMsg),
+                Request::SubscribeCommand("hello".to_string(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn unsubscribe_on_stop() {
+        let (core, conn) = MockCore::with_timeouts();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.subscribe(EventType::PrivMsg, |_, _| ());
+        dazeus.subscribe(EventType::Join, |_, _| ());
+        dazeus.subscribe(EventType::PrivMsg, |_, _| ());
+        dazeus.subscribe_command("hello", |_, _| ());
+        dazeus.set_unsubscribe_on_stop(true);
+        core.clear_requests();
+
+        dazeus.stop();
+        dazeus.listen().unwrap();
+        assert_eq!(
+            core.requests(),
+            [
+                Request::Unsubscribe(EventType::PrivMsg),
+                Request::Unsubscribe(EventType::Join)
+            ]
+        );
+        // the listeners are kept for when listening starts again
+        assert!(dazeus.has_any_subscription(EventType::PrivMsg));
+    }
+
+    #[test]
+    fn queued_messages_are_sent_on_stop() {
+        let (core, conn) = MockCore::with_timeouts();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.set_rate_limit(Some(RateLimit::new(1, Duration::from_millis(10))));
+        for text in &["1", "2", "3"] {
+            dazeus.message("local", "#a", text);
         }
+        assert_eq!(dazeus.queued_messages(), 2);
+
+        dazeus.stop();
+        dazeus.listen().unwrap();
+        assert_eq!(dazeus.queued_messages(), 0);
+        assert_eq!(
+            core.requests(),
+            ["1", "2", "3"]
+                .iter()
+                .map(|text| Request::Message(
+                    "local".to_string(),
+                    "#a".to_string(),
+                    text.to_string()
+                ))
+                .collect::<Vec<_>>()
+        );
     }
 
-    /// Send a reply (as a notice) in response to some event.
-    ///
-    /// Note that not all types of events can be responded to. Mostly message type events
-    /// concerning some IRC user can be responded to. Join events can also be responded to.
-    fn reply_with_notice(&self, event: &Event, message: &str) -> Response {
-        if let Some((network, channel, user)) = targets_for_event(event) {
-            let nick = self.nick(network).unwrap_or_else(|| "".to_string());
-            if channel == nick {
-                self.notice(network, user, message)
-            } else {
-                self.notice(network, channel, message)
+    #[test]
+    fn stop_from_callback() {
+        let (core, conn) = MockCore::with_timeouts();
+        let mut dazeus = DaZeus::new(conn);
+        let handled = Rc::new(Cell::new(0));
+        let counter = handled.clone();
+        dazeus.subscribe(EventType::PrivMsg, move |_, dazeus| {
+            counter.set(counter.get() + 1);
+            dazeus.stop();
+        });
+        core.inject_event(message("first"));
+        core.inject_event(message("second"));
+
+        dazeus.listen().unwrap();
+        assert_eq!(handled.get(), 1);
+    }
+
+    #[test]
+    fn help_output() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.enable_help();
+        dazeus.describe_command(
+            CommandSpec::new("greet")
+                .help("Greet someone")
+                .arg("who", ArgType::Text),
+        );
+        dazeus.describe_command(CommandSpec::new("bye"));
+
+        let help = |args: &str| {
+            core.clear_requests();
+            core.respond(response(json!({"success": true, "value": "!"})));
+            core.inject_event(event(
+                EventType::Command("help".to_string()),
+                &["local", "alice", "#channel", "help", args],
+            ));
+            dazeus.try_next_event().unwrap();
+            match core.requests().last() {
+                Some(Request::Notice(_, target, msg)) if target == "#channel" => msg.clone(),
+                other => panic!("Expected a notice, got {:?}", other),
             }
-        } else {
-            Response::for_fail("Not an event to reply to")
+        };
+
+        assert_eq!(
+            help(""),
+            "Available commands: bye, greet, help. Use !help <command> for more information."
+        );
+        assert_eq!(help("greet"), "!greet <who> - Greet someone");
+        // the highlight character may be given along with the name
+        assert_eq!(help("!greet"), "!greet <who> - Greet someone");
+        assert_eq!(help("bye"), "!bye");
+        assert_eq!(help("unknown"), "Unknown command: unknown");
+    }
+
+    #[test]
+    fn permission_denied_reply() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let runs = Rc::new(Cell::new(0));
+        let counter = runs.clone();
+        dazeus.subscribe_command_with_permission("op", "channel.op", false, move |_, _| {
+            counter.set(counter.get() + 1)
+        });
+
+        let op = |has_permission: bool| {
+            core.clear_requests();
+            core.respond(response(
+                json!({"success": true, "has_permission": has_permission}),
+            ));
+            core.inject_event(event(
+                EventType::Command("op".to_string()),
+                &["local", "alice", "#channel", "op", ""],
+            ));
+            dazeus.try_next_event().unwrap();
+            core.requests()
+        };
+
+        let requests = op(true);
+        assert_eq!(runs.get(), 1);
+        assert_eq!(
+            requests,
+            [Request::HasPermission(
+                "channel.op".to_string(),
+                false,
+                Scope::to("local", "alice", "#channel")
+            )]
+        );
+
+        let requests = op(false);
+        assert_eq!(runs.get(), 1);
+        assert_eq!(
+            requests.last(),
+            Some(&Request::Message(
+                "local".to_string(),
+                "#channel".to_string(),
+                "alice: Permission denied".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn permission_denied_without_reply() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.set_permission_denied_reply(None);
+        dazeus.subscribe_command_with_permission("op", "channel.op", false, |_, _| {
+            panic!("Permission should have been denied")
+        });
+        core.clear_requests();
+
+        core.inject_event(event(
+            EventType::Command("op".to_string()),
+            &["local", "alice", "#channel", "op", ""],
+        ));
+        dazeus.try_next_event().unwrap();
+        assert_eq!(core.requests().len(), 1);
+    }
+
+    #[test]
+    fn cascading_property_stops_at_first_value() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let scope = Scope::to("local", "alice", "#channel");
+        let channel = Scope::new(
+            Some("local".to_string()),
+            None,
+            Some("#channel".to_string()),
+        );
+        let get = |scope: &Scope| Request::GetProperty("greeting".to_string(), scope.clone());
+
+        core.respond(Response::for_success());
+        core.respond(response(json!({"success": true, "value": "hi"})));
+        assert_eq!(
+            dazeus.get_property_cascading("greeting", scope.clone()),
+            Some(("hi".to_string(), channel.clone()))
+        );
+        assert_eq!(core.requests(), [get(&scope), get(&channel)]);
+
+        core.clear_requests();
+        assert_eq!(
+            dazeus.get_property_cascading("greeting", scope.clone()),
+            None
+        );
+        assert_eq!(
+            core.requests(),
+            [
+                get(&scope),
+                get(&channel),
+                get(&Scope::network("local")),
+                get(&Scope::any())
+            ]
+        );
+    }
+
+    #[test]
+    fn import_rejects_non_string_values() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let properties = json!({"a": "1", "b": 2, "c": "3"});
+
+        let result = dazeus.try_import_properties(&properties, Scope::any());
+        assert!(matches!(result, Err(Error::InvalidJsonError(_))));
+        assert!(dazeus
+            .try_import_properties(&json!(["a"]), Scope::any())
+            .is_err());
+        assert!(core.requests().is_empty());
+
+        let properties = json!({"a": "1", "c": "3"});
+        assert_eq!(
+            dazeus
+                .try_import_properties(&properties, Scope::any())
+                .unwrap(),
+            2
+        );
+        assert_eq!(core.requests().len(), 2);
+    }
+
+    #[test]
+    fn flush_gives_up_after_timeout() {
+        let (core, conn) = MockCore::with_timeouts();
+        let mut dazeus = DaZeus::new(conn);
+        dazeus.set_rate_limit(Some(RateLimit::new(0, Duration::from_secs(60))));
+        dazeus.set_flush_timeout(Duration::from_millis(50));
+        dazeus.message("local", "#a", "1");
+        let queued = dazeus.message("local", "#a", "2");
+        assert_eq!(queued.get_bool("queued"), Some(true));
+
+        dazeus.stop();
+        let started = Instant::now();
+        dazeus.listen().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(dazeus.queued_messages(), 1);
+        assert_eq!(core.requests().len(), 1);
+    }
+
+    #[test]
+    fn reply_without_sender_is_refused() {
+        let (_core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let evt = event(EventType::Connect, &["local"]);
+
+        match dazeus.try_reply(&evt, "hello", false) {
+            Err(Error::Refused { .. }) => (),
+            other => panic!("Expected a refusal, got {:?}", other),
         }
+        assert!(!dazeus.reply(&evt, "hello", false).has_success());
     }
 
-    /// Send a reply (as a CTCP action) in response to some event.
-    ///
-    /// Note that not all types of events can be responded to. Mostly message type events
-    /// concerning some IRC user can be responded to. Join events can also be responded to.
-    fn reply_with_action(&self, event: &Event, message: &str) -> Response {
-        if let Some((network, channel, user)) = targets_for_event(event) {
-            let nick = self.nick(network).unwrap_or_else(|| "".to_string());
-            if channel == nick {
-                self.action(network, user, message)
+    #[test]
+    fn nested_requests_from_several_events() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+
+        let inner = Rc::new(RefCell::new(Vec::new()));
+        let result = inner.clone();
+        dazeus.subscribe(EventType::PrivMsg, move |evt, dazeus| {
+            let nick = dazeus.try_nick(&evt[3]).unwrap();
+            result.borrow_mut().push(nick.unwrap());
+        });
+
+        core.inject_event(message("first"));
+        core.inject_event(message("second"));
+        core.respond(nick_response("Outer"));
+        core.respond(nick_response("First"));
+        core.respond(nick_response("Second"));
+
+        assert_eq!(dazeus.try_nick("outer").unwrap(), Some("Outer".to_string()));
+        assert_eq!(*inner.borrow(), vec!["First", "Second"]);
+    }
+
+    /// Subscribe a listener that logs the events it receives.
+    fn logger(
+        dazeus: &mut DaZeus<MockConnection>,
+        name: &'static str,
+        priority: i32,
+        log: &Rc<RefCell<Vec<String>>>,
+    ) {
+        let log = log.clone();
+        dazeus.subscribe_with_priority(EventType::PrivMsg, priority, move |evt, _| {
+            log.borrow_mut().push(format!("{} {}", name, &evt[3]));
+            Flow::Continue
+        });
+    }
+
+    /// Subscribe a listener that is still busy with "one" when "two" arrives, and stops "two".
+    fn busy_listener(
+        dazeus: &mut DaZeus<MockConnection>,
+        core: &MockCore,
+        priority: i32,
+        log: &Rc<RefCell<Vec<String>>>,
+    ) {
+        let (core, log) = (core.clone(), log.clone());
+        dazeus.subscribe_with_priority(EventType::PrivMsg, priority, move |evt, dazeus| {
+            log.borrow_mut().push(format!("busy {}", &evt[3]));
+            if &evt[3] == "one" {
+                // the next event arrives before the response to this request
+                core.inject_event(message("two"));
+                dazeus.try_nick("local").unwrap();
+                Flow::Continue
             } else {
-                self.action(network, channel, message)
+                Flow::Stop
             }
-        } else {
-            Response::for_fail("Not an event to reply to")
+        });
+    }
+
+    #[test]
+    fn priorities_and_flow() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for &(name, priority) in [("low", -1), ("high", 10), ("default", 0)].iter() {
+            let log = log.clone();
+            dazeus.subscribe_with_priority(EventType::PrivMsg, priority, move |evt, _| {
+                log.borrow_mut().push(format!("{} {}", name, &evt[3]));
+                if &evt[3] == "stop" {
+                    Flow::Stop
+                } else {
+                    Flow::Continue
+                }
+            });
         }
+
+        core.inject_event(message("go"));
+        core.inject_event(message("stop"));
+        assert!(dazeus.listen().is_err());
+        assert_eq!(
+            *log.borrow(),
+            vec!["high go", "default go", "low go", "high stop"]
+        );
     }
-}
 
-fn targets_for_event(event: &Event) -> Option<(&str, &str, &str)> {
-    let params = &event.params;
-    match event.event {
-        EventType::Join
-        | EventType::PrivMsg
-        | EventType::Notice
-        | EventType::Ctcp
-        | EventType::Command(_)
-        | EventType::Action => Some((&params[0][..], &params[2][..], &params[1][..])),
-        _ => None,
+    #[test]
+    fn set_priority_reorders_listeners() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let first = log.clone();
+        let (handle, _) = dazeus.subscribe(EventType::PrivMsg, move |_, _| {
+            first.borrow_mut().push("first")
+        });
+        let second = log.clone();
+        dazeus.subscribe(EventType::PrivMsg, move |_, _| {
+            second.borrow_mut().push("second")
+        });
+
+        assert!(dazeus.set_priority(handle, -1));
+        assert!(!dazeus.set_priority(1000, -1));
+        core.inject_event(message("hello"));
+        assert!(dazeus.listen().is_err());
+        assert_eq!(*log.borrow(), vec!["second", "first"]);
+    }
+
+    #[test]
+    fn nested_event_waits_for_busy_listener() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        busy_listener(&mut dazeus, &core, 10, &log);
+        logger(&mut dazeus, "low", 0, &log);
+
+        core.inject_event(message("one"));
+        assert!(dazeus.listen().is_err());
+        assert_eq!(*log.borrow(), vec!["busy one", "low one", "busy two"]);
+    }
+
+    #[test]
+    fn nested_event_reaches_listeners_that_are_done() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        logger(&mut dazeus, "high", 20, &log);
+        busy_listener(&mut dazeus, &core, 10, &log);
+        logger(&mut dazeus, "low", 0, &log);
+
+        core.inject_event(message("one"));
+        assert!(dazeus.listen().is_err());
+        assert_eq!(
+            *log.borrow(),
+            vec!["high one", "busy one", "high two", "low one", "busy two"]
+        );
+    }
+
+    #[test]
+    fn nested_event_of_other_type_keeps_order() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let (inner, busy) = (core.clone(), log.clone());
+        dazeus.subscribe_with_priority(EventType::PrivMsg, 10, move |_, dazeus| {
+            busy.borrow_mut().push("busy PRIVMSG".to_string());
+            inner.inject_event(event(EventType::Join, &["local", "someone", "#channel"]));
+            dazeus.try_nick("local").unwrap();
+            Flow::Continue
+        });
+        let all = log.clone();
+        dazeus.subscribe_all(move |evt, _| all.borrow_mut().push(format!("all {}", evt.event)));
+
+        core.inject_event(message("one"));
+        assert!(dazeus.listen().is_err());
+        assert_eq!(
+            *log.borrow(),
+            vec!["busy PRIVMSG", "all PRIVMSG", "all JOIN"]
+        );
     }
 }
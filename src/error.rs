@@ -1,3 +1,4 @@
+use super::response::Response;
 use serde_json::error::Error as JsonError;
 use std::fmt::Error as FmtError;
 use std::fmt::{Display, Formatter};
@@ -19,6 +20,56 @@ impl InvalidJsonError {
     }
 }
 
+/// Error returned when an event did not have the parameters required for its type.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InvalidEventError {
+    message: String,
+}
+
+impl InvalidEventError {
+    /// Create a new error instance.
+    pub fn new(message: &str) -> InvalidEventError {
+        InvalidEventError {
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Error returned when the arguments given to a command did not match its `CommandSpec`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CommandParseError {
+    message: String,
+}
+
+impl CommandParseError {
+    /// Create a new error instance.
+    pub fn new(message: &str) -> CommandParseError {
+        CommandParseError {
+            message: message.to_string(),
+        }
+    }
+
+    /// Retrieve a description of what was wrong with the arguments, suitable for IRC users.
+    pub fn message(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+/// Error returned when an expected event or response did not arrive in time.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TimeoutError {
+    message: String,
+}
+
+impl TimeoutError {
+    /// Create a new error instance.
+    pub fn new(message: &str) -> TimeoutError {
+        TimeoutError {
+            message: message.to_string(),
+        }
+    }
+}
+
 /// Error returned when a string could not be parsed as an `EventType`.
 ///
 /// This may occur if an event is provided by DaZeus which is unknown by this implementation.
@@ -65,7 +116,26 @@ pub enum Error {
     IoError(IoError),
     Utf8Error(Utf8Error),
     InvalidJsonError(InvalidJsonError),
+    InvalidEventError(InvalidEventError),
+    CommandParseError(CommandParseError),
     ReceiveError(ReceiveError),
+    Timeout(TimeoutError),
+    /// The DaZeus core indicated that it could not fulfill a request.
+    CoreRejected {
+        /// A description of the request that was rejected.
+        request: String,
+        /// The reason given by the core.
+        reason: String,
+        /// The response of the core.
+        response: Response,
+    },
+    /// The request could not be fulfilled, so it was not sent to the DaZeus core.
+    Refused {
+        /// A description of the request that was refused.
+        request: String,
+        /// The reason why it could not be fulfilled.
+        reason: String,
+    },
 }
 
 impl From<IoError> for Error {
@@ -92,12 +162,30 @@ impl From<InvalidJsonError> for Error {
     }
 }
 
+impl From<InvalidEventError> for Error {
+    fn from(err: InvalidEventError) -> Error {
+        Error::InvalidEventError(err)
+    }
+}
+
+impl From<CommandParseError> for Error {
+    fn from(err: CommandParseError) -> Error {
+        Error::CommandParseError(err)
+    }
+}
+
 impl From<ReceiveError> for Error {
     fn from(err: ReceiveError) -> Error {
         Error::ReceiveError(err)
     }
 }
 
+impl From<TimeoutError> for Error {
+    fn from(err: TimeoutError) -> Error {
+        Error::Timeout(err)
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
         write!(f, "{:?}", self)
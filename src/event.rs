@@ -1,5 +1,6 @@
 use super::error::{InvalidJsonError, ParseEventTypeError};
 use serde_json::Value as JsonValue;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::ops::Index;
 use std::str::FromStr;
 
@@ -70,33 +71,66 @@ pub enum EventType {
     Whois,
 }
 
-impl ToString for EventType {
-    fn to_string(&self) -> String {
+impl EventType {
+    /// Retrieve all event types that can be subscribed to, except for commands.
+    ///
+    /// Commands are left out as they are subscribed to by name, as is `EventType::Unknown`, as
+    /// the core never sends events of an unknown type to plugins.
+    pub fn all_events() -> Vec<EventType> {
+        vec![
+            EventType::Action,
+            EventType::ActionMe,
+            EventType::Connect,
+            EventType::Ctcp,
+            EventType::CtcpMe,
+            EventType::CtcpReply,
+            EventType::Disconnect,
+            EventType::Invite,
+            EventType::Join,
+            EventType::Kick,
+            EventType::Mode,
+            EventType::Names,
+            EventType::Nick,
+            EventType::Notice,
+            EventType::Numeric,
+            EventType::Part,
+            EventType::Pong,
+            EventType::PrivMsg,
+            EventType::PrivMsgMe,
+            EventType::Quit,
+            EventType::Topic,
+            EventType::Whois,
+        ]
+    }
+}
+
+impl Display for EventType {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match *self {
-            EventType::Command(ref s) => format!("COMMAND_{}", s),
-            EventType::Action => "ACTION".to_string(),
-            EventType::ActionMe => "ACTION_ME".to_string(),
-            EventType::Connect => "CONNECT".to_string(),
-            EventType::Ctcp => "CTCP".to_string(),
-            EventType::CtcpMe => "CTCP_ME".to_string(),
-            EventType::CtcpReply => "CTCP_REP".to_string(),
-            EventType::Disconnect => "DISCONNECT".to_string(),
-            EventType::Invite => "INVITE".to_string(),
-            EventType::Join => "JOIN".to_string(),
-            EventType::Kick => "KICK".to_string(),
-            EventType::Mode => "MODE".to_string(),
-            EventType::Names => "NAMES".to_string(),
-            EventType::Nick => "NICK".to_string(),
-            EventType::Notice => "NOTICE".to_string(),
-            EventType::Numeric => "NUMERIC".to_string(),
-            EventType::Part => "PART".to_string(),
-            EventType::Pong => "PONG".to_string(),
-            EventType::PrivMsg => "PRIVMSG".to_string(),
-            EventType::PrivMsgMe => "PRIVMSG_ME".to_string(),
-            EventType::Quit => "QUIT".to_string(),
-            EventType::Topic => "TOPIC".to_string(),
-            EventType::Unknown => "UNKNOWN".to_string(),
-            EventType::Whois => "WHOIS".to_string(),
+            EventType::Command(ref s) => write!(f, "COMMAND_{}", s),
+            EventType::Action => f.write_str("ACTION"),
+            EventType::ActionMe => f.write_str("ACTION_ME"),
+            EventType::Connect => f.write_str("CONNECT"),
+            EventType::Ctcp => f.write_str("CTCP"),
+            EventType::CtcpMe => f.write_str("CTCP_ME"),
+            EventType::CtcpReply => f.write_str("CTCP_REP"),
+            EventType::Disconnect => f.write_str("DISCONNECT"),
+            EventType::Invite => f.write_str("INVITE"),
+            EventType::Join => f.write_str("JOIN"),
+            EventType::Kick => f.write_str("KICK"),
+            EventType::Mode => f.write_str("MODE"),
+            EventType::Names => f.write_str("NAMES"),
+            EventType::Nick => f.write_str("NICK"),
+            EventType::Notice => f.write_str("NOTICE"),
+            EventType::Numeric => f.write_str("NUMERIC"),
+            EventType::Part => f.write_str("PART"),
+            EventType::Pong => f.write_str("PONG"),
+            EventType::PrivMsg => f.write_str("PRIVMSG"),
+            EventType::PrivMsgMe => f.write_str("PRIVMSG_ME"),
+            EventType::Quit => f.write_str("QUIT"),
+            EventType::Topic => f.write_str("TOPIC"),
+            EventType::Unknown => f.write_str("UNKNOWN"),
+            EventType::Whois => f.write_str("WHOIS"),
         }
     }
 }
@@ -146,7 +180,9 @@ impl FromStr for EventType {
 /// 2. Using the `param()` method with an index which will return a string slice.
 /// 3. Using indexing on the event struct itself, i.e. `event[0]` to receive the first parameter.
 ///
-/// The prefered method is the last one.
+/// The prefered method is the last one. Alternatively, `Event::as_typed()` provides a view on the
+/// event with named fields instead of positional parameters, which also checks that all
+/// parameters required for the event type are present.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Event {
     /// The type of event that was received.
@@ -191,7 +227,7 @@ impl Event {
                 let evt = obj.get("event").unwrap();
                 let params = obj.get("params").unwrap();
                 if evt.is_string() && params.is_array() {
-                    Event::create_event(evt.as_str().unwrap(), &params.as_array().unwrap())
+                    Event::create_event(evt.as_str().unwrap(), params.as_array().unwrap())
                 } else {
                     Err(InvalidJsonError::new(""))
                 }
@@ -234,6 +270,26 @@ impl Event {
         strs
     }
 
+    /// Create a Json object for this event, as it would be sent by the DaZeus core.
+    pub fn to_json(&self) -> JsonValue {
+        let mut obj = serde_json::Map::new();
+        let name = match self.event {
+            EventType::Command(_) => "COMMAND".to_string(),
+            ref evt => evt.to_string(),
+        };
+        obj.insert("event".to_string(), JsonValue::String(name));
+        obj.insert(
+            "params".to_string(),
+            JsonValue::Array(
+                self.params
+                    .iter()
+                    .map(|p| JsonValue::String(p.clone()))
+                    .collect(),
+            ),
+        );
+        JsonValue::Object(obj)
+    }
+
     /// Retrieve a parameter from the list of parameters contained in the event.
     pub fn param(&self, idx: usize) -> &str {
         &self.params[idx][..]
@@ -250,7 +306,7 @@ impl Event {
     }
 }
 
-impl<'b> Index<usize> for Event {
+impl Index<usize> for Event {
     type Output = str;
 
     fn index(&self, index: usize) -> &str {
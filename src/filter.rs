@@ -0,0 +1,329 @@
+use super::dazeus::DaZeusClient;
+use super::event::Event;
+use super::scope::Scope;
+use super::typed::TypedEvent;
+#[cfg(feature = "regex")]
+use regex::Regex;
+use std::ops::Not;
+
+type Predicate = Box<dyn Fn(&Event, &dyn DaZeusClient) -> bool>;
+
+/// A condition that events have to match before they are passed to a listener.
+///
+/// Filters are created using `EventFilter::new()`, which matches every event, and narrowed down
+/// by calling the methods below. An event has to match every condition that was added to the
+/// filter. Filters can be combined further using `EventFilter::or()` and negated using `!`.
+///
+/// Network, channel and sender names are compared case-insensitively using the IRC rules, so
+/// `#DaZeus` and `#dazeus` are considered the same channel.
+///
+/// # Example
+/// ```no_run
+/// # use dazeus::*;
+/// # use std::str::FromStr;
+/// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+/// let filter = EventFilter::new()
+///     .network("freenode")
+///     .channel("#ops")
+///     .message(|msg| msg.starts_with("!deploy"))
+///     .ignore_self();
+///
+/// dazeus.subscribe_filtered(EventType::PrivMsg, filter, |evt, dazeus| {
+///     dazeus.reply(&evt, "Deploying!", true);
+/// });
+/// ```
+pub struct EventFilter {
+    conditions: Vec<Predicate>,
+}
+
+impl EventFilter {
+    /// Create a filter that matches every event.
+    pub fn new() -> EventFilter {
+        EventFilter {
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Only match events for which the given function returns true.
+    pub fn matching<F>(mut self, condition: F) -> EventFilter
+    where
+        F: Fn(&Event, &dyn DaZeusClient) -> bool + 'static,
+    {
+        self.conditions.push(Box::new(condition));
+        self
+    }
+
+    /// Only match events that occurred on the given network.
+    pub fn network(self, network: &str) -> EventFilter {
+        let network = network.to_string();
+        self.matching(move |evt, _| match Scope::from_event(evt) {
+            Ok(Scope {
+                network: Some(ref n),
+                ..
+            }) => irc_eq(n, &network),
+            _ => false,
+        })
+    }
+
+    /// Only match events that occurred in the given channel.
+    ///
+    /// Events that did not occur in a channel (such as a `QUIT`) never match.
+    pub fn channel(self, channel: &str) -> EventFilter {
+        let channel = channel.to_string();
+        self.matching(move |evt, _| match Scope::from_event(evt) {
+            Ok(Scope {
+                receiver: Some(ref c),
+                ..
+            }) => irc_eq(c, &channel),
+            _ => false,
+        })
+    }
+
+    /// Only match events caused by a user matching the given pattern.
+    ///
+    /// The pattern may contain the wildcards `*` and `?`. A hostmask such as `alice!*@*` may be
+    /// given, but only the nick part is used, as the DaZeus core does not include the user and
+    /// host in events.
+    pub fn sender(self, pattern: &str) -> EventFilter {
+        let pattern = irc_lowercase(pattern.split('!').next().unwrap_or(""));
+        self.matching(move |evt, _| match Scope::from_event(evt) {
+            Ok(Scope {
+                sender: Some(ref s),
+                ..
+            }) => wildcard_match(&pattern, &irc_lowercase(s)),
+            _ => false,
+        })
+    }
+
+    /// Only match events with a message for which the given function returns true.
+    ///
+    /// The message is the text of a message, action, notice or CTCP, the arguments of a command,
+    /// or the new topic of a channel. Events without a message never match.
+    ///
+    /// Any kind of matching can be used in the condition. To match messages using a regular
+    /// expression, enable the `regex` feature and use `EventFilter::message_regex()`.
+    pub fn message<F>(self, condition: F) -> EventFilter
+    where
+        F: Fn(&str) -> bool + 'static,
+    {
+        self.matching(move |evt, _| match evt.as_typed() {
+            Ok(TypedEvent::Action(e))
+            | Ok(TypedEvent::ActionMe(e))
+            | Ok(TypedEvent::Ctcp(e))
+            | Ok(TypedEvent::CtcpMe(e))
+            | Ok(TypedEvent::CtcpReply(e))
+            | Ok(TypedEvent::Notice(e))
+            | Ok(TypedEvent::PrivMsg(e))
+            | Ok(TypedEvent::PrivMsgMe(e)) => condition(&e.message),
+            Ok(TypedEvent::Command(e)) => condition(&e.rest),
+            Ok(TypedEvent::Topic(e)) => condition(&e.topic),
+            _ => false,
+        })
+    }
+
+    /// Only match events with a message that matches the given regular expression.
+    ///
+    /// See `EventFilter::message()` for which events have a message. Requires the `regex`
+    /// feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use dazeus::*;
+    /// # use regex::Regex;
+    /// let filter = EventFilter::new().message_regex(Regex::new("^!deploy( |$)").unwrap());
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn message_regex(self, regex: Regex) -> EventFilter {
+        self.message(move |msg| regex.is_match(msg))
+    }
+
+    /// Don't match events caused by the bot itself.
+    ///
+    /// The nick of the bot is retrieved using `DaZeusClient::try_cached_nick()`, so `DaZeus` only
+    /// asks the core for it once per network.
+    pub fn ignore_self(self) -> EventFilter {
+        self.matching(|evt, dazeus| match Scope::from_event(evt) {
+            Ok(Scope {
+                network: Some(ref network),
+                sender: Some(ref sender),
+                ..
+            }) => match dazeus.try_cached_nick(network) {
+                Ok(Some(ref nick)) => !irc_eq(nick, sender),
+                _ => true,
+            },
+            _ => true,
+        })
+    }
+
+    /// Match events that match either this filter or the other filter.
+    pub fn or(self, other: EventFilter) -> EventFilter {
+        EventFilter::new()
+            .matching(move |evt, dazeus| self.matches(evt, dazeus) || other.matches(evt, dazeus))
+    }
+
+    /// Check whether an event matches all conditions of this filter.
+    pub fn matches(&self, event: &Event, dazeus: &dyn DaZeusClient) -> bool {
+        self.conditions.iter().all(|c| c(event, dazeus))
+    }
+}
+
+/// Negating a filter creates a filter that matches exactly those events the original does not.
+impl Not for EventFilter {
+    type Output = EventFilter;
+
+    fn not(self) -> EventFilter {
+        EventFilter::new().matching(move |evt, dazeus| !self.matches(evt, dazeus))
+    }
+}
+
+impl Default for EventFilter {
+    fn default() -> EventFilter {
+        EventFilter::new()
+    }
+}
+
+/// Convert a nick or channel name to lowercase using the RFC 1459 case mapping.
+fn irc_lowercase(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '[' => '{',
+            ']' => '}',
+            '\\' => '|',
+            '~' => '^',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+fn irc_eq(a: &str, b: &str) -> bool {
+    irc_lowercase(a) == irc_lowercase(b)
+}
+
+/// Match some text against a pattern containing `*` and `?` wildcards.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            p = bp + 1;
+            t = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dazeus::DaZeus;
+    use crate::testing::{privmsg, response, MockConnection, MockCore};
+    use serde_json::json;
+
+    fn dazeus() -> (MockCore, DaZeus<'static, MockConnection>) {
+        let (core, conn) = MockCore::new();
+        (core, DaZeus::new(conn))
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("alice", "alice"));
+        assert!(!wildcard_match("alice", "alicia"));
+        assert!(wildcard_match("a?ice", "alice"));
+        assert!(!wildcard_match("a?ice", "aice"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*", "a"));
+        assert!(wildcard_match("*bot", "dazeusbot"));
+        assert!(!wildcard_match("*bot", "dazeusbots"));
+        assert!(wildcard_match("*a*b", "xaxxab"));
+        assert!(wildcard_match("a*b*c", "abbbc"));
+        assert!(wildcard_match("a*bc", "abcbc"));
+        assert!(!wildcard_match("a*bc", "abcb"));
+        assert!(wildcard_match("**?", "x"));
+        assert!(!wildcard_match("?", ""));
+    }
+
+    #[test]
+    fn rfc1459_case_folding() {
+        assert_eq!(irc_lowercase("Nick[Away]"), "nick{away}");
+        assert_eq!(irc_lowercase("a\\b~c"), "a|b^c");
+        assert!(irc_eq("#DaZeus", "#dazeus"));
+        assert!(irc_eq("[foo]\\~", "{FOO}|^"));
+        assert!(!irc_eq("foo_", "foo-"));
+    }
+
+    #[test]
+    fn channel_and_sender() {
+        let (_core, dazeus) = dazeus();
+        let filter = EventFilter::new().channel("#DaZeus").sender("Al[i]ce!*@*");
+        assert!(filter.matches(&privmsg("local", "al{i}ce", "#dazeus", "hi"), &dazeus));
+        assert!(!filter.matches(&privmsg("local", "bob", "#dazeus", "hi"), &dazeus));
+        assert!(!filter.matches(&privmsg("local", "alice", "#other", "hi"), &dazeus));
+    }
+
+    #[test]
+    fn or_and_not() {
+        let (_core, dazeus) = dazeus();
+        let ops = EventFilter::new().channel("#ops");
+        let either = EventFilter::new()
+            .channel("#ops")
+            .or(EventFilter::new().sender("admin"));
+        let outside = !EventFilter::new().channel("#ops");
+
+        let in_ops = privmsg("local", "bob", "#ops", "hi");
+        let by_admin = privmsg("local", "admin", "#other", "hi");
+        let neither = privmsg("local", "bob", "#other", "hi");
+
+        assert!(ops.matches(&in_ops, &dazeus));
+        assert!(either.matches(&in_ops, &dazeus));
+        assert!(either.matches(&by_admin, &dazeus));
+        assert!(!either.matches(&neither, &dazeus));
+        assert!(!outside.matches(&in_ops, &dazeus));
+        assert!(outside.matches(&neither, &dazeus));
+    }
+
+    #[test]
+    fn deploy_in_ops() {
+        let (core, dazeus) = dazeus();
+        let filter = EventFilter::new()
+            .network("freenode")
+            .channel("#ops")
+            .message(|msg| msg.starts_with("!deploy"))
+            .ignore_self();
+
+        assert!(filter.matches(
+            &privmsg("freenode", "alice", "#OPS", "!deploy now"),
+            &dazeus
+        ));
+        assert!(!filter.matches(
+            &privmsg("freenode", "alice", "#ops", "please !deploy"),
+            &dazeus
+        ));
+        assert!(!filter.matches(&privmsg("oftc", "alice", "#ops", "!deploy"), &dazeus));
+
+        core.respond(response(json!({"success": true, "nick": "DaZeus"})));
+        assert!(!filter.matches(&privmsg("freenode", "dazeus", "#ops", "!deploy"), &dazeus));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn message_regex() {
+        let (_core, dazeus) = dazeus();
+        let filter = EventFilter::new().message_regex(Regex::new("^!deploy( |$)").unwrap());
+
+        assert!(filter.matches(&privmsg("local", "alice", "#ops", "!deploy"), &dazeus));
+        assert!(filter.matches(&privmsg("local", "alice", "#ops", "!deploy now"), &dazeus));
+        assert!(!filter.matches(&privmsg("local", "alice", "#ops", "!deployed"), &dazeus));
+    }
+}
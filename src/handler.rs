@@ -1,10 +1,10 @@
-use super::error::Error;
+use super::error::{Error, TimeoutError};
 use super::event::{is_event_json, Event};
 use super::request::Request;
 use super::response::Response;
 use log::debug;
 use std::borrow::ToOwned;
-use std::io::{Read, Write};
+use std::io::{Error as IoError, ErrorKind, Read, Write};
 use std::str::from_utf8;
 
 pub enum Message {
@@ -14,7 +14,7 @@ pub enum Message {
 
 pub struct Handler<T> {
     socket: T,
-    buffer: Vec<u8>,
+    buffer: Buffer,
 }
 
 impl<T> Handler<T>
@@ -24,14 +24,14 @@ where
     pub fn new(socket: T) -> Handler<T> {
         Handler {
             socket,
-            buffer: Vec::new(),
+            buffer: Buffer::new(),
         }
     }
 
     pub fn read(&mut self) -> Result<Message, Error> {
         loop {
-            if let Some((offset, len)) = self.find_message() {
-                return self.make_message(offset, len);
+            if let Some(message) = self.buffer.next_message() {
+                return message;
             }
 
             self.retrieve_from_socket()?;
@@ -41,34 +41,67 @@ where
     /// Retrieve new data from the socket
     fn retrieve_from_socket(&mut self) -> Result<(), Error> {
         let mut buf = [0; 1024];
-        let bytes = self.socket.read(&mut buf)?;
-        for b in buf[..bytes].iter() {
-            self.buffer.push(*b);
+        let bytes = self
+            .socket
+            .read(&mut buf)
+            .map_err(|e| timeout_error(e, "Timed out waiting for data from DaZeus"))?;
+        if bytes == 0 {
+            return Err(Error::IoError(closed_error()));
         }
+        self.buffer.extend(&buf[..bytes]);
         Ok(())
     }
 
+    pub fn write(&mut self, request: Request) -> Result<(), Error> {
+        self.socket
+            .write_all(&encode(&request))
+            .map_err(|e| timeout_error(e, "Timed out sending data to DaZeus"))?;
+        Ok(())
+    }
+}
+
+/// Collects data received from DaZeus and splits it into messages.
+pub struct Buffer {
+    data: Vec<u8>,
+}
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer { data: Vec::new() }
+    }
+
+    /// Add data received from DaZeus to the buffer.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Take the next complete message from the buffer, if there is one.
+    pub fn next_message(&mut self) -> Option<Result<Message, Error>> {
+        self.find_message()
+            .map(|(offset, len)| self.make_message(offset, len))
+    }
+
     /// Find where a message is located
     fn find_message(&self) -> Option<(usize, usize)> {
         let mut offset = 0;
         let mut message_len = 0;
 
-        while offset < self.buffer.len() {
+        while offset < self.data.len() {
             // check for a number
-            if self.buffer[offset] < 0x3A && self.buffer[offset] >= 0x30 {
+            if self.data[offset] < 0x3A && self.data[offset] >= 0x30 {
                 message_len *= 10;
-                message_len += (self.buffer[offset] - 0x30) as usize;
+                message_len += (self.data[offset] - 0x30) as usize;
                 offset += 1;
 
             // skip newline and carriage return
-            } else if self.buffer[offset] == 0xa || self.buffer[offset] == 0xd {
+            } else if self.data[offset] == 0xa || self.data[offset] == 0xd {
                 offset += 1;
             } else {
                 break;
             }
         }
 
-        if message_len > 0 && self.buffer.len() >= offset + message_len {
+        if message_len > 0 && self.data.len() >= offset + message_len {
             debug!(
                 "Found message in buffer starting at {} with length {}",
                 offset, message_len
@@ -82,16 +115,16 @@ where
 
     fn make_message(&mut self, offset: usize, length: usize) -> Result<Message, Error> {
         let end = offset + length;
-        assert!(self.buffer.len() >= end);
+        assert!(self.data.len() >= end);
 
         // check the result of our conversion
-        let json_try = match from_utf8(&self.buffer[offset..end]) {
+        let json_try = match from_utf8(&self.data[offset..end]) {
             Ok(json_str) => Ok(serde_json::from_str(json_str)),
             Err(e) => Err(e),
         };
 
         // first make sure we have a correct internal state
-        self.buffer = self.buffer[offset + length..].to_owned(); // iter().collect();
+        self.data = self.data[offset + length..].to_owned(); // iter().collect();
 
         let json = json_try??;
 
@@ -105,15 +138,28 @@ where
             Ok(Message::Response(resp))
         }
     }
+}
 
-    pub fn write(&mut self, request: Request) -> Result<(), Error> {
-        let encoded = request.to_json().to_string();
-        debug!("Sending message: {}", encoded);
+/// Encode a request as a length-prefixed message that can be sent to DaZeus.
+pub fn encode(request: &Request) -> Vec<u8> {
+    let encoded = request.to_json().to_string();
+    debug!("Sending message: {}", encoded);
 
-        let bytes = encoded.as_bytes();
-        self.socket
-            .write_all(format!("{}", bytes.len()).as_bytes())?;
-        self.socket.write_all(bytes)?;
-        Ok(())
+    let bytes = encoded.as_bytes();
+    let mut message = format!("{}", bytes.len()).into_bytes();
+    message.extend_from_slice(bytes);
+    message
+}
+
+/// The error returned when DaZeus closes the connection.
+pub fn closed_error() -> IoError {
+    IoError::new(ErrorKind::UnexpectedEof, "Connection closed by DaZeus")
+}
+
+/// Convert errors caused by a read or write timeout on the socket to `Error::Timeout`.
+fn timeout_error(err: IoError, message: &str) -> Error {
+    match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => Error::Timeout(TimeoutError::new(message)),
+        _ => Error::IoError(err),
     }
 }
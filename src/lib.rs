@@ -19,12 +19,23 @@
 //! methods provided. Alternatively you can send Request objects directly using the
 //! `DaZeusClient::send()` method, however this is generally not recommended.
 //!
+//! Note that these methods panic whenever communication with the DaZeus core fails. When the core
+//! could not fulfill a request, they return its response instead. Every one of them has a `try_`
+//! counterpart (such as `DaZeusClient::try_message()`) that returns a `Result` instead, which is
+//! the better choice for long-running plugins.
+//!
 //! You can register new listeners using the `DaZeus::subscribe()` and
 //! `DaZeus::subscribe_command()` methods. You provide these with functions which will be called
-//! every time such an event occurs.
+//! every time such an event occurs. Use `DaZeus::subscribe_filtered()` with an `EventFilter` to
+//! only receive events from some network, channel or user.
 //!
 //! After you have enabled any event subscribers you need to use the `DaZeus::listen()` method,
-//! or check for new events manually using `DaZeus::try_next_event()`.
+//! or check for new events manually using `DaZeus::try_next_event()`. Listening can be stopped
+//! using a `StopHandle`, or on SIGINT and SIGTERM by enabling the `signals` feature.
+//!
+//! Plugins can be tested without a running DaZeus core by enabling the `testing` feature, which
+//! provides an in-memory core in the `testing` module. Plugins built on Tokio can use
+//! `AsyncDaZeus` instead of `DaZeus` by enabling the `async` feature.
 //!
 //! # Examples
 //! The example below creates a simple echo server which responds to some PrivMsg with the exact
@@ -55,21 +66,45 @@
 //! dazeus.join("local", "#test");
 //! ```
 
+#[cfg(feature = "async")]
+pub use self::async_dazeus::{AsyncDaZeus, EventStream};
+pub use self::command::*;
 pub use self::connection::*;
 pub use self::dazeus::*;
 pub use self::error::*;
 pub use self::event::*;
-pub use self::listener::ListenerHandle;
+pub use self::filter::EventFilter;
+pub use self::listener::{Flow, ListenerHandle};
+pub use self::properties::*;
+pub use self::ratelimit::RateLimit;
+pub use self::reconnect::*;
+pub use self::remote::DaZeusHandle;
 pub use self::request::*;
 pub use self::response::*;
+pub use self::schedule::TaskHandle;
 pub use self::scope::*;
+pub use self::stop::StopHandle;
+pub use self::typed::*;
 
+#[cfg(feature = "async")]
+mod async_dazeus;
+mod command;
 mod connection;
 mod dazeus;
 mod error;
 mod event;
+mod filter;
 mod handler;
 mod listener;
+mod properties;
+mod ratelimit;
+mod reconnect;
+mod remote;
 mod request;
 mod response;
+mod schedule;
 mod scope;
+mod stop;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod typed;
@@ -1,18 +1,34 @@
 use super::dazeus::{DaZeus, DaZeusClient};
 use super::event::{Event, EventType};
+use super::filter::EventFilter;
 use std::cell::RefCell;
 use std::fmt::{Debug, Error, Formatter};
 use std::io::{Read, Write};
 use std::ops::DerefMut;
+use std::rc::Rc;
 
 /// An identifier for unsubscribing an event listener.
 pub type ListenerHandle = u64;
 
+/// Whether an event should be passed on to the next listener.
+///
+/// Listeners registered using `DaZeus::subscribe_with_priority()` return this to indicate whether
+/// the listeners with a lower priority should also receive the event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    /// Pass the event on to the next listener.
+    Continue,
+    /// Don't pass the event on to any other listeners.
+    Stop,
+}
+
 pub struct Listener<'a> {
     pub event: EventType,
     pub handle: ListenerHandle,
+    pub filter: Option<EventFilter>,
+    pub priority: i32,
     #[allow(clippy::type_complexity)]
-    callback: RefCell<Box<dyn FnMut(Event, &dyn DaZeusClient) + 'a>>,
+    callback: Rc<RefCell<Box<dyn FnMut(Event, &dyn DaZeusClient) -> Flow + 'a>>>,
 }
 
 impl<'a> PartialEq for Listener<'a> {
@@ -25,8 +41,8 @@ impl<'a> Debug for Listener<'a> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(
             f,
-            "Listener {{ event: {:?}, handle: {:?} callback: FnMut(Event) }}",
-            self.event, self.handle
+            "Listener {{ event: {:?}, handle: {:?}, priority: {:?} callback: FnMut(Event) }}",
+            self.event, self.handle, self.priority
         )
     }
 }
@@ -34,19 +50,44 @@ impl<'a> Debug for Listener<'a> {
 impl<'a> Listener<'a> {
     pub fn new<F>(handle: ListenerHandle, event_type: EventType, listener: F) -> Listener<'a>
     where
-        F: FnMut(Event, &dyn DaZeusClient) + 'a,
+        F: FnMut(Event, &dyn DaZeusClient) -> Flow + 'a,
     {
         Listener {
             event: event_type,
             handle,
-            callback: RefCell::new(Box::new(listener)),
+            filter: None,
+            priority: 0,
+            callback: Rc::new(RefCell::new(Box::new(listener))),
         }
     }
 
-    pub fn call<T: Read + Write>(&self, event: Event, dazeus: &DaZeus<T>) {
-        let mut fbox = self.callback.borrow_mut();
+    /// Create a listener for another event type that shares the callback of this listener.
+    pub fn for_event(&self, event_type: EventType) -> Listener<'a> {
+        Listener {
+            event: event_type,
+            handle: self.handle,
+            filter: None,
+            priority: self.priority,
+            callback: self.callback.clone(),
+        }
+    }
+
+    /// Call the listener with some event.
+    ///
+    /// Returns `None` if the callback is still busy handling an earlier event, which happens when
+    /// the callback sends a request and another event arrives before the response.
+    pub fn call<T: Read + Write>(&self, event: Event, dazeus: &DaZeus<T>) -> Option<Flow> {
+        let mut fbox = self.callback.try_borrow_mut().ok()?;
         let func = fbox.deref_mut();
-        func(event, dazeus as &dyn DaZeusClient);
+        Some(func(event, dazeus as &dyn DaZeusClient))
+    }
+
+    pub fn matches<T: Read + Write>(&self, event: &Event, dazeus: &DaZeus<T>) -> bool {
+        self.event == event.event
+            && match self.filter {
+                Some(ref filter) => filter.matches(event, dazeus),
+                None => true,
+            }
     }
 
     pub fn has_handle(&self, handle: ListenerHandle) -> bool {
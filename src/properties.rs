@@ -0,0 +1,356 @@
+use super::dazeus::{unwrap_or_panic, unwrap_or_rejected, unwrap_response, DaZeusClient};
+use super::error::Error;
+use super::response::Response;
+use super::scope::Scope;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Methods for storing structured values as properties in the bot database.
+///
+/// Values are encoded as JSON before they are stored, so anything that implements `Serialize`
+/// and `Deserialize` can be stored, such as counters, lists and structs. This trait is
+/// implemented for every `DaZeusClient`, including the `&dyn DaZeusClient` given to listeners.
+///
+/// # Example
+/// ```no_run
+/// # use dazeus::*;
+/// # use std::str::FromStr;
+/// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+/// dazeus.subscribe_command("karma", |evt, dazeus| {
+///     let scope = Scope::network(&evt[0]);
+///     let karma = dazeus.update_property("karma.total", scope, |k: Option<i64>| k.unwrap_or(0) + 1);
+///     dazeus.reply(&evt, &format!("Total karma: {}", karma), true);
+/// });
+/// ```
+pub trait PropertyExt<'a>: DaZeusClient<'a> {
+    /// Try to retrieve a property stored in the bot database and decode it from JSON.
+    ///
+    /// Returns `None` if the property was not set for the given scope.
+    fn try_get_property_as<T>(&self, name: &str, scope: Scope) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        match self.try_get_property(name, scope)? {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Try to encode a value as JSON and store it as a property in the bot database.
+    fn try_set_property_as<T>(&self, name: &str, value: &T, scope: Scope) -> Result<Response, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let encoded = serde_json::to_string(value)?;
+        self.try_set_property(name, &encoded, scope)
+    }
+
+    /// Try to update a property stored in the bot database using the given function.
+    ///
+    /// The function receives the current value (if any) and returns the new value, which is
+    /// stored and returned. Note that the property is read and written using two separate
+    /// requests, so another plugin may change the property in between.
+    fn try_update_property<T, F>(&self, name: &str, scope: Scope, update: F) -> Result<T, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(Option<T>) -> T,
+    {
+        let current = self.try_get_property_as(name, scope.clone())?;
+        let value = update(current);
+        self.try_set_property_as(name, &value, scope)?;
+        Ok(value)
+    }
+
+    /// Retrieve a property stored in the bot database and decode it from JSON.
+    ///
+    /// Returns `None` if DaZeus rejected the request. Panics if the request could not be sent or
+    /// the property could not be decoded, see `PropertyExt::try_get_property_as()` for a
+    /// non-panicking alternative.
+    fn get_property_as<T>(&self, name: &str, scope: Scope) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        unwrap_or_rejected(self.try_get_property_as(name, scope), |_| None)
+    }
+
+    /// Encode a value as JSON and store it as a property in the bot database.
+    ///
+    /// Panics if the request could not be sent or the value could not be encoded, see
+    /// `PropertyExt::try_set_property_as()` for a non-panicking alternative.
+    fn set_property_as<T>(&self, name: &str, value: &T, scope: Scope) -> Response
+    where
+        T: Serialize + ?Sized,
+    {
+        unwrap_response(self.try_set_property_as(name, value, scope))
+    }
+
+    /// Update a property stored in the bot database using the given function.
+    ///
+    /// Panics if the property could not be retrieved, decoded or stored, including when DaZeus
+    /// rejected the request, see `PropertyExt::try_update_property()` for a non-panicking
+    /// alternative.
+    fn update_property<T, F>(&self, name: &str, scope: Scope, update: F) -> T
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(Option<T>) -> T,
+    {
+        unwrap_or_panic(self.try_update_property(name, scope, update))
+    }
+}
+
+impl<'a, C> PropertyExt<'a> for C where C: DaZeusClient<'a> + ?Sized {}
+
+/// A handle for storing properties under a fixed prefix and with a default scope.
+///
+/// Property names are global to the DaZeus core, so plugins using the same names would overwrite
+/// each other's properties. A property store prefixes every name with a namespace (by default
+/// the name of the plugin given in the handshake) so plugins don't collide.
+///
+/// The store does not hold on to a connection, instead every method takes the client to use.
+/// This allows the store to be moved into listener callbacks.
+///
+/// # Example
+/// ```no_run
+/// # use dazeus::*;
+/// # use std::str::FromStr;
+/// # let mut dazeus = DaZeus::new(Connection::from_str("unix:/tmp/dazeus.sock").unwrap());
+/// dazeus.handshake("quotes", PROTOCOL_VERSION, None);
+/// let store = dazeus.property_store().unwrap();
+///
+/// dazeus.subscribe_command("addquote", move |evt, dazeus| {
+///     // stored as `quotes.last` in the core
+///     store.in_scope(Scope::network(&evt[0])).set(dazeus, "last", &evt[4]);
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyStore {
+    prefix: String,
+    scope: Scope,
+}
+
+impl PropertyStore {
+    /// Create a new store that prefixes every property name with the given prefix.
+    ///
+    /// The prefix and the property name are separated by a dot.
+    pub fn new(prefix: &str, scope: Scope) -> PropertyStore {
+        PropertyStore {
+            prefix: prefix.to_string(),
+            scope,
+        }
+    }
+
+    /// Create a store with the same prefix that uses another scope.
+    pub fn in_scope(&self, scope: Scope) -> PropertyStore {
+        PropertyStore::new(&self.prefix, scope)
+    }
+
+    /// Retrieve the prefix used by this store.
+    pub fn prefix(&self) -> &str {
+        &self.prefix[..]
+    }
+
+    /// Retrieve the scope used by this store.
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}.{}", self.prefix, name)
+    }
+
+    /// Try to retrieve a property from the store.
+    pub fn try_get<'a, C>(&self, client: &C, name: &str) -> Result<Option<String>, Error>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        client.try_get_property(&self.key(name), self.scope.clone())
+    }
+
+    /// Try to store a property.
+    pub fn try_set<'a, C>(&self, client: &C, name: &str, value: &str) -> Result<Response, Error>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        client.try_set_property(&self.key(name), value, self.scope.clone())
+    }
+
+    /// Try to remove a property from the store.
+    pub fn try_unset<'a, C>(&self, client: &C, name: &str) -> Result<Response, Error>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        client.try_unset_property(&self.key(name), self.scope.clone())
+    }
+
+    /// Try to retrieve the names of the properties in the store starting with some prefix.
+    ///
+    /// The names are returned without the prefix of the store, so they can be passed to the
+    /// other methods of the store directly.
+    pub fn try_keys<'a, C>(&self, client: &C, prefix: &str) -> Result<Vec<String>, Error>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        let namespace = self.key("");
+        let keys = client.try_get_property_keys(&self.key(prefix), self.scope.clone())?;
+        Ok(keys
+            .into_iter()
+            .map(|k| match k.strip_prefix(&namespace[..]) {
+                Some(stripped) => stripped.to_string(),
+                None => k,
+            })
+            .collect())
+    }
+
+    /// Try to retrieve a property from the store and decode it from JSON.
+    pub fn try_get_as<'a, C, T>(&self, client: &C, name: &str) -> Result<Option<T>, Error>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+        T: DeserializeOwned,
+    {
+        client.try_get_property_as(&self.key(name), self.scope.clone())
+    }
+
+    /// Try to encode a value as JSON and store it.
+    pub fn try_set_as<'a, C, T>(&self, client: &C, name: &str, value: &T) -> Result<Response, Error>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+        T: Serialize + ?Sized,
+    {
+        client.try_set_property_as(&self.key(name), value, self.scope.clone())
+    }
+
+    /// Retrieve a property from the store.
+    ///
+    /// Returns `None` if DaZeus rejected the request. Panics if the request could not be sent,
+    /// see `PropertyStore::try_get()` for a non-panicking alternative.
+    pub fn get<'a, C>(&self, client: &C, name: &str) -> Option<String>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        unwrap_or_rejected(self.try_get(client, name), |_| None)
+    }
+
+    /// Store a property.
+    ///
+    /// Panics if the request could not be sent, see `PropertyStore::try_set()` for a
+    /// non-panicking alternative.
+    pub fn set<'a, C>(&self, client: &C, name: &str, value: &str) -> Response
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        unwrap_response(self.try_set(client, name, value))
+    }
+
+    /// Remove a property from the store.
+    ///
+    /// Panics if the request could not be sent, see `PropertyStore::try_unset()` for a
+    /// non-panicking alternative.
+    pub fn unset<'a, C>(&self, client: &C, name: &str) -> Response
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        unwrap_response(self.try_unset(client, name))
+    }
+
+    /// Retrieve the names of the properties in the store starting with some prefix.
+    ///
+    /// Returns no names if DaZeus rejected the request. Panics if the request could not be sent,
+    /// see `PropertyStore::try_keys()` for a non-panicking alternative.
+    pub fn keys<'a, C>(&self, client: &C, prefix: &str) -> Vec<String>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+    {
+        unwrap_or_rejected(self.try_keys(client, prefix), |_| Vec::new())
+    }
+
+    /// Retrieve a property from the store and decode it from JSON.
+    ///
+    /// Returns `None` if DaZeus rejected the request. Panics if the request could not be sent or
+    /// the property could not be decoded, see `PropertyStore::try_get_as()` for a non-panicking
+    /// alternative.
+    pub fn get_as<'a, C, T>(&self, client: &C, name: &str) -> Option<T>
+    where
+        C: DaZeusClient<'a> + ?Sized,
+        T: DeserializeOwned,
+    {
+        unwrap_or_rejected(self.try_get_as(client, name), |_| None)
+    }
+
+    /// Encode a value as JSON and store it.
+    ///
+    /// Panics if the request could not be sent or the value could not be encoded, see
+    /// `PropertyStore::try_set_as()` for a non-panicking alternative.
+    pub fn set_as<'a, C, T>(&self, client: &C, name: &str, value: &T) -> Response
+    where
+        C: DaZeusClient<'a> + ?Sized,
+        T: Serialize + ?Sized,
+    {
+        unwrap_response(self.try_set_as(client, name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dazeus::DaZeus;
+    use crate::request::Request;
+    use crate::testing::{response, MockCore};
+    use serde_json::json;
+
+    #[test]
+    fn update_property_round_trips() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let scope = Scope::network("local");
+
+        // the property is not set yet
+        core.respond(Response::for_success());
+        let count =
+            dazeus.update_property("count", scope.clone(), |c: Option<i64>| c.unwrap_or(0) + 1);
+        assert_eq!(count, 1);
+
+        core.clear_requests();
+        core.respond(response(json!({"success": true, "value": "1"})));
+        let count =
+            dazeus.update_property("count", scope.clone(), |c: Option<i64>| c.unwrap_or(0) + 1);
+        assert_eq!(count, 2);
+        assert_eq!(
+            core.requests(),
+            [
+                Request::GetProperty("count".to_string(), scope.clone()),
+                Request::SetProperty("count".to_string(), "2".to_string(), scope.clone())
+            ]
+        );
+
+        core.clear_requests();
+        core.respond(response(json!({"success": true, "value": "[\"a\"]"})));
+        let list = dazeus.update_property("list", scope.clone(), |l: Option<Vec<String>>| {
+            let mut l = l.unwrap();
+            l.push("b".to_string());
+            l
+        });
+        assert_eq!(list, ["a", "b"]);
+        assert_eq!(
+            core.requests()[1],
+            Request::SetProperty("list".to_string(), "[\"a\",\"b\"]".to_string(), scope)
+        );
+    }
+
+    #[test]
+    fn store_keys_strip_prefix() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let store = PropertyStore::new("quotes", Scope::network("local"));
+        core.respond(response(
+            json!({"success": true, "keys": ["quotes.last", "quotes.list.1", "quotesx"]}),
+        ));
+
+        assert_eq!(store.keys(&dazeus, "l"), ["last", "list.1", "quotesx"]);
+        assert_eq!(
+            core.requests(),
+            [Request::PropertyKeys(
+                "quotes.l".to_string(),
+                Scope::network("local")
+            )]
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use super::request::Request;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Settings for limiting the rate at which messages are sent to IRC.
+///
+/// When rate limiting is enabled using `DaZeus::set_rate_limit()`, messages, notices, actions and
+/// CTCPs are held back when they are sent too quickly, so the bot does not get kicked for
+/// flooding. Every combination of network and target (a channel or user) has its own token
+/// bucket: up to `burst` messages can be sent at once, after which one message can be sent every
+/// `interval`. A limit for all targets on a network together can be set using
+/// `DaZeus::set_network_rate_limit()`.
+///
+/// # Example
+/// ```
+/// # use dazeus::RateLimit;
+/// # use std::time::Duration;
+/// // send at most 3 messages at once, and then one every second
+/// let limit = RateLimit::new(3, Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    /// The number of messages that can be sent at once.
+    ///
+    /// A burst of zero is treated as a burst of one, as no message could ever be sent otherwise.
+    pub burst: u32,
+
+    /// The time it takes before another message can be sent.
+    pub interval: Duration,
+}
+
+impl RateLimit {
+    /// Create a new rate limit.
+    pub fn new(burst: u32, interval: Duration) -> RateLimit {
+        RateLimit { burst, interval }
+    }
+}
+
+impl Default for RateLimit {
+    /// Allow a burst of five messages, followed by one message every two seconds.
+    fn default() -> RateLimit {
+        RateLimit::new(5, Duration::from_secs(2))
+    }
+}
+
+/// A token bucket, holding a token for every message that may be sent right away.
+struct Tokens {
+    tokens: u32,
+    refilled: Instant,
+}
+
+impl Tokens {
+    fn new(limit: Option<&RateLimit>) -> Tokens {
+        Tokens {
+            // without a limit any number of messages may be sent, until a limit is set
+            tokens: limit.map_or(u32::MAX, |limit| limit.burst),
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Add the tokens that became available since the last refill.
+    fn refill(&mut self, limit: &RateLimit) {
+        if self.tokens >= limit.burst {
+            self.refilled = Instant::now();
+            return;
+        }
+
+        let elapsed = self.refilled.elapsed();
+        let new_tokens = match limit.interval.as_nanos() {
+            0 => u128::from(limit.burst),
+            interval => elapsed.as_nanos() / interval,
+        };
+        if new_tokens > 0 {
+            // new_tokens is at most a few times the burst here, so it always fits
+            let new_tokens = new_tokens.min(u128::from(limit.burst)) as u32;
+            self.tokens = (self.tokens + new_tokens).min(limit.burst);
+            self.refilled += limit.interval * new_tokens;
+        }
+    }
+
+    /// Don't allow more messages at once than the (new) limit does.
+    fn restrict(&mut self, limit: &RateLimit) {
+        self.tokens = self.tokens.min(limit.burst);
+    }
+
+    /// Whether a token is available under the given limit (if any).
+    fn available(&mut self, limit: Option<&RateLimit>) -> bool {
+        match limit {
+            Some(limit) => {
+                self.refill(limit);
+                self.tokens > 0
+            }
+            None => true,
+        }
+    }
+
+    /// Use up a token, which should be available.
+    fn take(&mut self, limit: Option<&RateLimit>) {
+        if limit.is_some() {
+            self.tokens -= 1;
+        }
+    }
+
+    /// Retrieve how long it takes before the next token becomes available.
+    fn next_token(&self, limit: Option<&RateLimit>) -> Duration {
+        match limit {
+            Some(limit) if self.tokens == 0 => {
+                (self.refilled + limit.interval).saturating_duration_since(Instant::now())
+            }
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    /// Whether a full burst could be sent again.
+    fn is_full(&mut self, limit: Option<&RateLimit>) -> bool {
+        match limit {
+            Some(limit) => {
+                self.refill(limit);
+                self.tokens >= limit.burst
+            }
+            None => true,
+        }
+    }
+}
+
+/// The token bucket and queued requests for a single network and target.
+struct Bucket {
+    tokens: Tokens,
+    queue: VecDeque<Request>,
+}
+
+/// Keeps track of the outgoing messages for every network and target.
+///
+/// A message is only sent when a token is available both for its target and for its network.
+/// Without a rate limit, requests are only queued if earlier requests for the same target are
+/// still waiting, so the order of messages is kept when the rate limit is removed.
+pub(crate) struct RateLimiter {
+    limit: Option<RateLimit>,
+    network_limit: Option<RateLimit>,
+    buckets: HashMap<(String, String), Bucket>,
+    networks: HashMap<String, Tokens>,
+}
+
+impl RateLimiter {
+    pub fn new() -> RateLimiter {
+        RateLimiter {
+            limit: None,
+            network_limit: None,
+            buckets: HashMap::new(),
+            networks: HashMap::new(),
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: Option<RateLimit>) {
+        let limit = limit.map(at_least_one);
+        if let Some(ref limit) = limit {
+            for bucket in self.buckets.values_mut() {
+                bucket.tokens.restrict(limit);
+            }
+        }
+        self.limit = limit;
+    }
+
+    pub fn set_network_limit(&mut self, limit: Option<RateLimit>) {
+        let limit = limit.map(at_least_one);
+        if let Some(ref limit) = limit {
+            for tokens in self.networks.values_mut() {
+                tokens.restrict(limit);
+            }
+        }
+        self.network_limit = limit;
+    }
+
+    /// Retrieve the network and target of requests that are subject to rate limiting.
+    fn key(request: &Request) -> Option<(String, String)> {
+        match *request {
+            Request::Message(ref network, ref target, _)
+            | Request::Notice(ref network, ref target, _)
+            | Request::Action(ref network, ref target, _)
+            | Request::Ctcp(ref network, ref target, _) => Some((network.clone(), target.clone())),
+            _ => None,
+        }
+    }
+
+    /// Submit a request to be sent.
+    ///
+    /// Returns the request if it can be sent right away, otherwise it is queued.
+    pub fn submit(&mut self, request: Request) -> Option<Request> {
+        let key = match RateLimiter::key(&request) {
+            Some(key) => key,
+            None => return Some(request),
+        };
+
+        let limit = self.limit.as_ref();
+        let network_limit = self.network_limit.as_ref();
+        let network = self
+            .networks
+            .entry(key.0.clone())
+            .or_insert_with(|| Tokens::new(network_limit));
+        let bucket = self.buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: Tokens::new(limit),
+            queue: VecDeque::new(),
+        });
+
+        if bucket.queue.is_empty()
+            && bucket.tokens.available(limit)
+            && network.available(network_limit)
+        {
+            bucket.tokens.take(limit);
+            network.take(network_limit);
+            return Some(request);
+        }
+        bucket.queue.push_back(request);
+        None
+    }
+
+    /// Take all queued requests that may be sent now.
+    pub fn take_ready(&mut self) -> Vec<Request> {
+        let limit = self.limit.as_ref();
+        let network_limit = self.network_limit.as_ref();
+        let mut ready = Vec::new();
+
+        // take a single message per target at a time, so targets on a network take turns
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for ((network, _), bucket) in self.buckets.iter_mut() {
+                if bucket.queue.is_empty() {
+                    continue;
+                }
+                let network = self
+                    .networks
+                    .entry(network.clone())
+                    .or_insert_with(|| Tokens::new(network_limit));
+                if bucket.tokens.available(limit) && network.available(network_limit) {
+                    bucket.tokens.take(limit);
+                    network.take(network_limit);
+                    ready.extend(bucket.queue.pop_front());
+                    progress = true;
+                }
+            }
+        }
+
+        // forget about targets and networks that have nothing queued and could send a full burst
+        self.buckets
+            .retain(|_, bucket| !bucket.queue.is_empty() || !bucket.tokens.is_full(limit));
+        self.networks
+            .retain(|_, tokens| !tokens.is_full(network_limit));
+        ready
+    }
+
+    /// Retrieve how long it takes before the next queued request may be sent (if any).
+    pub fn next_ready(&self) -> Option<Duration> {
+        let limit = self.limit.as_ref();
+        let network_limit = self.network_limit.as_ref();
+        self.buckets
+            .iter()
+            .filter(|(_, bucket)| !bucket.queue.is_empty())
+            .map(|((network, _), bucket)| {
+                let network = self
+                    .networks
+                    .get(network)
+                    .map_or(Duration::from_secs(0), |tokens| {
+                        tokens.next_token(network_limit)
+                    });
+                bucket.tokens.next_token(limit).max(network)
+            })
+            .min()
+    }
+
+    /// Retrieve the number of queued requests.
+    pub fn queued(&self) -> usize {
+        self.buckets.values().map(|bucket| bucket.queue.len()).sum()
+    }
+
+    /// Retrieve the number of queued requests for some network and target.
+    pub fn queued_for(&self, network: &str, target: &str) -> usize {
+        self.buckets
+            .get(&(network.to_string(), target.to_string()))
+            .map_or(0, |bucket| bucket.queue.len())
+    }
+}
+
+/// Allow at least one message at once, as no message could ever be sent with a burst of zero.
+fn at_least_one(limit: RateLimit) -> RateLimit {
+    RateLimit {
+        burst: limit.burst.max(1),
+        ..limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn message(target: &str, text: &str) -> Request {
+        Request::Message("local".to_string(), target.to_string(), text.to_string())
+    }
+
+    fn limiter(burst: u32, interval: Duration) -> RateLimiter {
+        let mut limiter = RateLimiter::new();
+        limiter.set_limit(Some(RateLimit::new(burst, interval)));
+        limiter
+    }
+
+    #[test]
+    fn burst() {
+        let mut limiter = limiter(2, Duration::from_secs(60));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        assert!(limiter.submit(message("#a", "2")).is_some());
+        assert!(limiter.submit(message("#a", "3")).is_none());
+        assert_eq!(limiter.queued(), 1);
+        assert!(limiter.take_ready().is_empty());
+        assert!(limiter.next_ready().unwrap() > Duration::from_secs(59));
+    }
+
+    #[test]
+    fn other_requests_are_not_limited() {
+        let mut limiter = limiter(1, Duration::from_secs(60));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        let join = Request::Join("local".to_string(), "#a".to_string());
+        assert_eq!(limiter.submit(join.clone()), Some(join));
+    }
+
+    #[test]
+    fn refill() {
+        let mut limiter = limiter(1, Duration::from_millis(20));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        assert!(limiter.submit(message("#a", "2")).is_none());
+        assert!(limiter.submit(message("#a", "3")).is_none());
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(limiter.take_ready(), vec![message("#a", "2")]);
+        assert_eq!(limiter.queued(), 1);
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(limiter.take_ready(), vec![message("#a", "3")]);
+        assert_eq!(limiter.next_ready(), None);
+    }
+
+    #[test]
+    fn targets_are_separate() {
+        let mut limiter = limiter(1, Duration::from_secs(60));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        assert!(limiter.submit(message("#a", "2")).is_none());
+        assert!(limiter.submit(message("#b", "1")).is_some());
+        assert!(limiter
+            .submit(Request::Message(
+                "other".to_string(),
+                "#a".to_string(),
+                "1".to_string()
+            ))
+            .is_some());
+
+        assert_eq!(limiter.queued_for("local", "#a"), 1);
+        assert_eq!(limiter.queued_for("local", "#b"), 0);
+        assert_eq!(limiter.queued(), 1);
+    }
+
+    #[test]
+    fn order_is_kept_when_limit_is_removed() {
+        let mut limiter = limiter(1, Duration::from_secs(60));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        assert!(limiter.submit(message("#a", "2")).is_none());
+        assert!(limiter.submit(message("#a", "3")).is_none());
+
+        limiter.set_limit(None);
+        assert_eq!(limiter.next_ready(), Some(Duration::from_secs(0)));
+
+        // new messages wait for the ones that were already queued
+        assert!(limiter.submit(message("#a", "4")).is_none());
+        assert!(limiter.submit(message("#b", "1")).is_some());
+        assert_eq!(
+            limiter.take_ready(),
+            vec![message("#a", "2"), message("#a", "3"), message("#a", "4")]
+        );
+        assert!(limiter.submit(message("#a", "5")).is_some());
+    }
+
+    #[test]
+    fn idle_buckets_are_removed() {
+        let mut limiter = limiter(2, Duration::from_millis(10));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        limiter.take_ready();
+        assert_eq!(limiter.buckets.len(), 1);
+
+        thread::sleep(Duration::from_millis(20));
+        limiter.take_ready();
+        assert!(limiter.buckets.is_empty());
+    }
+
+    #[test]
+    fn zero_burst_allows_one_message() {
+        let mut limiter = limiter(0, Duration::from_millis(10));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        assert!(limiter.submit(message("#a", "2")).is_none());
+
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(limiter.take_ready(), vec![message("#a", "2")]);
+    }
+
+    #[test]
+    fn network_limit_applies_to_all_targets() {
+        let mut limiter = RateLimiter::new();
+        limiter.set_network_limit(Some(RateLimit::new(2, Duration::from_millis(20))));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        assert!(limiter.submit(message("#b", "1")).is_some());
+        assert!(limiter.submit(message("#c", "1")).is_none());
+        assert!(limiter.submit(message("#a", "2")).is_none());
+        let other = Request::Message("other".to_string(), "#a".to_string(), "1".to_string());
+        assert!(limiter.submit(other).is_some());
+        assert!(limiter.take_ready().is_empty());
+
+        // the queued targets take turns
+        thread::sleep(Duration::from_millis(30));
+        let ready = limiter.take_ready();
+        assert_eq!(ready.len(), 1);
+        thread::sleep(Duration::from_millis(30));
+        let mut ready = vec![ready[0].clone(), limiter.take_ready()[0].clone()];
+        ready.sort_by_key(|request| format!("{:?}", request));
+        assert_eq!(ready, vec![message("#a", "2"), message("#c", "1")]);
+    }
+
+    #[test]
+    fn lower_burst_applies_to_existing_buckets() {
+        let mut limiter = limiter(5, Duration::from_secs(60));
+        assert!(limiter.submit(message("#a", "1")).is_some());
+        limiter.set_limit(Some(RateLimit::new(1, Duration::from_secs(60))));
+        assert!(limiter.submit(message("#a", "2")).is_some());
+        assert!(limiter.submit(message("#a", "3")).is_none());
+    }
+}
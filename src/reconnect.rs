@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+/// Settings for automatically reconnecting to the DaZeus core.
+///
+/// When reconnecting is enabled using `DaZeus::set_reconnect()`, the bindings will try to
+/// re-establish the connection whenever it is lost while listening for events. Between failed
+/// attempts the bindings wait for an increasing amount of time, starting at the initial delay
+/// and doubling every attempt until the maximum delay has been reached.
+///
+/// # Example
+/// ```
+/// # use dazeus::ReconnectPolicy;
+/// # use std::time::Duration;
+/// let policy = ReconnectPolicy::new(Duration::from_millis(500), Duration::from_secs(30))
+///     .with_max_attempts(10);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// The time to wait before the second attempt to reconnect.
+    pub initial_delay: Duration,
+
+    /// The maximum time to wait between two attempts.
+    pub max_delay: Duration,
+
+    /// The number of attempts after which reconnecting is given up (if any).
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Create a new policy that keeps trying to reconnect forever.
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay,
+            max_delay,
+            max_attempts: None,
+        }
+    }
+
+    /// Give up reconnecting after the given number of attempts.
+    pub fn with_max_attempts(mut self, attempts: u32) -> ReconnectPolicy {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Retrieve the time to wait after the given (zero-based) failed attempt.
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        match self.initial_delay.checked_mul(factor) {
+            Some(delay) if delay < self.max_delay => delay,
+            _ => self.max_delay,
+        }
+    }
+
+    /// Check whether another attempt may be made after the given number of attempts.
+    pub fn allows_attempt(&self, attempts: u32) -> bool {
+        match self.max_attempts {
+            Some(max) => attempts < max,
+            None => true,
+        }
+    }
+}
+
+impl Default for ReconnectPolicy {
+    /// Start with a delay of one second, and wait at most a minute between attempts.
+    fn default() -> ReconnectPolicy {
+        ReconnectPolicy::new(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
@@ -0,0 +1,333 @@
+use super::dazeus::DaZeusClient;
+use super::error::Error;
+use super::event::{Event, EventType};
+use super::listener::ListenerHandle;
+use super::request::Request;
+use super::response::Response;
+use super::stop::StopHandle;
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+/// How often a handle waiting for a result checks whether `DaZeus::listen()` is still running.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Something a `DaZeusHandle` asks the thread running `DaZeus` to do.
+pub(crate) enum Call {
+    Send(Request, Sender<Result<Response, Error>>),
+    Whois(String, String, Sender<Result<Event, Error>>),
+    Names(String, String, Sender<Result<Event, Error>>),
+}
+
+impl Call {
+    /// Answer the call with an error without handling it.
+    fn reject(self) {
+        // the handle may have given up waiting, in which case the result is not needed
+        match self {
+            Call::Send(_, reply) => {
+                let _ = reply.send(Err(not_listening()));
+            }
+            Call::Whois(_, _, reply) | Call::Names(_, _, reply) => {
+                let _ = reply.send(Err(not_listening()));
+            }
+        }
+    }
+}
+
+/// Keeps track of the thread that is running `DaZeus::listen()` (if any).
+#[derive(Clone)]
+pub(crate) struct Listening {
+    thread: Arc<Mutex<Option<ThreadId>>>,
+}
+
+impl Listening {
+    pub fn new() -> Listening {
+        Listening {
+            thread: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Mark the current thread as running `DaZeus::listen()`, until the guard is dropped.
+    pub fn start<'r>(&'r self, calls: &'r Receiver<Call>) -> ListeningGuard<'r> {
+        // a handle may have passed its call just after the previous guard was dropped, in which
+        // case it stopped waiting for the result, so the call should not be handled anymore
+        reject_calls(calls);
+        self.set(Some(thread::current().id()));
+        ListeningGuard {
+            listening: self,
+            calls,
+        }
+    }
+
+    fn set(&self, thread: Option<ThreadId>) {
+        // the lock is never held while anything can panic, but don't give up if it was poisoned
+        let mut current = self.thread.lock().unwrap_or_else(|e| e.into_inner());
+        *current = thread;
+    }
+
+    /// Check whether a handle can wait for `DaZeus::listen()` to handle its calls.
+    fn check(&self) -> Result<(), Error> {
+        let current = self.thread.lock().unwrap_or_else(|e| e.into_inner());
+        match *current {
+            Some(id) if id == thread::current().id() => Err(Error::IoError(IoError::new(
+                ErrorKind::WouldBlock,
+                "A DaZeusHandle can't be used from the thread running DaZeus::listen()",
+            ))),
+            Some(_) => Ok(()),
+            None => Err(not_listening()),
+        }
+    }
+}
+
+/// Marks a thread as running `DaZeus::listen()` for as long as it exists.
+///
+/// When dropped (also when `DaZeus::listen()` returns early or a callback panics), calls that have
+/// not been handled yet are rejected.
+pub(crate) struct ListeningGuard<'r> {
+    listening: &'r Listening,
+    calls: &'r Receiver<Call>,
+}
+
+impl<'r> Drop for ListeningGuard<'r> {
+    fn drop(&mut self) {
+        self.listening.set(None);
+        reject_calls(self.calls);
+    }
+}
+
+/// Reject all calls that are waiting to be handled.
+fn reject_calls(calls: &Receiver<Call>) {
+    while let Ok(call) = calls.try_recv() {
+        call.reject();
+    }
+}
+
+/// A handle for sending requests to DaZeus from other threads.
+///
+/// `DaZeus` itself can only be used from the thread that created it. A handle can be created
+/// using `DaZeus::handle()` and moved to (or cloned for) any other thread, for example a timer
+/// or a thread receiving webhooks. Requests sent using the handle are passed to the thread
+/// running `DaZeus::listen()`, which sends them to the core and passes back the response.
+///
+/// `DaZeus::listen()` checks for requests from handles whenever it receives an event, so the
+/// connection should have a read timeout to make sure requests are handled in time (see
+/// `Connection::set_read_timeout()`). Requests fail with an `Error::IoError` if `DaZeus` is not
+/// listening (before `DaZeus::listen()` is called, after it has returned, or after `DaZeus` has
+/// been dropped).
+///
+/// A handle can't be used from the thread running `DaZeus::listen()` itself, as that thread would
+/// then wait for itself. Requests made from listener callbacks or scheduled tasks fail with an
+/// `Error::IoError`; use the `DaZeusClient` these are given instead.
+///
+/// Handles implement `DaZeusClient`, so all the usual methods can be used. Listeners can only be
+/// added or removed by the thread running `DaZeus`.
+///
+/// # Example
+/// ```no_run
+/// # use dazeus::*;
+/// # use std::str::FromStr;
+/// # use std::thread;
+/// # use std::time::Duration;
+/// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+/// conn.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+/// let dazeus = DaZeus::new(conn);
+///
+/// let handle = dazeus.handle();
+/// thread::spawn(move || loop {
+///     thread::sleep(Duration::from_secs(3600));
+///     handle.message("freenode", "#dazeus", "Another hour has passed!");
+/// });
+///
+/// dazeus.listen();
+/// ```
+#[derive(Clone)]
+pub struct DaZeusHandle {
+    sender: Sender<Call>,
+    listening: Listening,
+    stop: StopHandle,
+}
+
+impl DaZeusHandle {
+    pub(crate) fn new(
+        sender: Sender<Call>,
+        listening: Listening,
+        stop: StopHandle,
+    ) -> DaZeusHandle {
+        DaZeusHandle {
+            sender,
+            listening,
+            stop,
+        }
+    }
+
+    /// Pass a call to the thread running DaZeus and wait for the result.
+    fn call<R, F>(&self, make_call: F) -> Result<R, Error>
+    where
+        F: FnOnce(Sender<Result<R, Error>>) -> Call,
+    {
+        self.listening.check()?;
+        let (reply, result) = channel();
+        self.sender
+            .send(make_call(reply))
+            .map_err(|_| not_listening())?;
+
+        loop {
+            match result.recv_timeout(POLL_INTERVAL) {
+                Ok(result) => return result,
+                Err(RecvTimeoutError::Disconnected) => return Err(not_listening()),
+                // DaZeus may have stopped listening before it saw the call
+                Err(RecvTimeoutError::Timeout) => self.listening.check()?,
+            }
+        }
+    }
+}
+
+fn not_listening() -> Error {
+    Error::IoError(IoError::new(
+        ErrorKind::NotConnected,
+        "DaZeus is not listening",
+    ))
+}
+
+fn not_removable(request: String) -> Error {
+    Error::Refused {
+        request,
+        reason: "Listeners can only be removed by the thread running DaZeus".to_string(),
+    }
+}
+
+impl<'a> DaZeusClient<'a> for DaZeusHandle {
+    /// Try to send a request to DaZeus using the thread running DaZeus.
+    fn try_send(&self, request: Request) -> Result<Response, Error> {
+        self.call(|reply| Call::Send(request, reply))
+    }
+
+    /// Listeners can't be removed using a handle, so this always fails.
+    fn try_unsubscribe(&mut self, handle: ListenerHandle) -> Result<Response, Error> {
+        Err(not_removable(format!("Unsubscribe listener {}", handle)))
+    }
+
+    /// Listeners can't be removed using a handle, so this always fails.
+    fn try_unsubscribe_all(&mut self, event: EventType) -> Result<Response, Error> {
+        Err(not_removable(format!(
+            "Unsubscribe all listeners for {}",
+            event
+        )))
+    }
+
+    /// Handles don't have any listeners, so this always returns false.
+    fn has_any_subscription(&self, _event: EventType) -> bool {
+        false
+    }
+
+    /// Stop `DaZeus::listen()` in the thread running DaZeus.
+    fn stop(&self) {
+        self.stop.stop();
+    }
+
+    /// Try to send a whois request and wait for an event that answers this request (blocking).
+    fn try_whois(&mut self, network: &str, nick: &str) -> Result<Event, Error> {
+        self.call(|reply| Call::Whois(network.to_string(), nick.to_string(), reply))
+    }
+
+    /// Try to send a names request and wait for an event that answers this request (blocking).
+    fn try_names(&mut self, network: &str, channel: &str) -> Result<Event, Error> {
+        self.call(|reply| Call::Names(network.to_string(), channel.to_string(), reply))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dazeus::DaZeus;
+    use crate::request::Request;
+    use crate::testing::{privmsg, MockCore};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn assert_fails<T: std::fmt::Debug>(result: Result<T, Error>, kind: ErrorKind) {
+        match result {
+            Err(Error::IoError(ref e)) if e.kind() == kind => (),
+            other => panic!("Expected an {:?} error, got {:?}", kind, other),
+        }
+    }
+
+    #[test]
+    fn fails_when_not_listening() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let handle = dazeus.handle();
+        assert_fails(handle.try_networks(), ErrorKind::NotConnected);
+
+        // the mock core closes the connection once everything has been read
+        assert!(dazeus.listen().is_err());
+        assert_fails(handle.try_networks(), ErrorKind::NotConnected);
+
+        drop(dazeus);
+        assert_fails(handle.try_networks(), ErrorKind::NotConnected);
+        assert!(core.requests().is_empty());
+    }
+
+    #[test]
+    fn fails_on_listening_thread() {
+        let (core, conn) = MockCore::new();
+        let mut dazeus = DaZeus::new(conn);
+        let handle = dazeus.handle();
+        let result = Rc::new(RefCell::new(None));
+        let inner = result.clone();
+        dazeus.subscribe(EventType::PrivMsg, move |_, _| {
+            *inner.borrow_mut() = Some(handle.try_networks());
+        });
+
+        core.inject_event(privmsg("local", "someone", "#channel", "hello"));
+        assert!(dazeus.listen().is_err());
+        assert_fails(result.borrow_mut().take().unwrap(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn stale_call_is_not_handled() {
+        let (core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let handle = dazeus.handle();
+
+        // a call that arrives after listening stopped, when its handle has stopped waiting
+        let (reply, result) = channel();
+        handle
+            .sender
+            .send(Call::Send(Request::Networks, reply))
+            .unwrap();
+
+        core.inject_event(privmsg("local", "someone", "#channel", "hello"));
+        assert!(dazeus.listen().is_err());
+        assert_fails(result.recv().unwrap(), ErrorKind::NotConnected);
+        assert!(core.requests().is_empty());
+    }
+
+    #[test]
+    fn sends_from_other_thread() {
+        let (core, conn) = MockCore::with_timeouts();
+        let dazeus = DaZeus::new(conn);
+        let handle = dazeus.handle();
+
+        let other = thread::spawn(move || {
+            // retry until the main thread is listening
+            let result = loop {
+                match handle.try_send(Request::Networks) {
+                    Err(Error::IoError(ref e)) if e.kind() == ErrorKind::NotConnected => {
+                        thread::sleep(Duration::from_millis(10))
+                    }
+                    result => break result,
+                }
+            };
+            handle.stop();
+            result
+        });
+
+        assert!(dazeus.listen().is_ok());
+        assert!(other.join().unwrap().unwrap().has_success());
+        assert_eq!(core.requests(), vec![Request::Networks]);
+        assert_fails(dazeus.handle().try_networks(), ErrorKind::NotConnected);
+    }
+}
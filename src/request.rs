@@ -1,9 +1,9 @@
-use super::error::ParseConfigGroupError;
+use super::error::{InvalidJsonError, ParseConfigGroupError};
 use super::event::EventType;
 use super::scope::Scope;
 use serde_json::Value as JsonValue;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::str::FromStr;
-use std::string::ToString;
 
 /// The version of the DaZeus plugin communication protocol that these bindings understand.
 pub const PROTOCOL_VERSION: &str = "1";
@@ -37,11 +37,11 @@ pub enum ConfigGroup {
     Core,
 }
 
-impl ToString for ConfigGroup {
-    fn to_string(&self) -> String {
+impl Display for ConfigGroup {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match *self {
-            ConfigGroup::Plugin => "plugin".to_string(),
-            ConfigGroup::Core => "core".to_string(),
+            ConfigGroup::Plugin => f.write_str("plugin"),
+            ConfigGroup::Core => f.write_str("core"),
         }
     }
 }
@@ -481,3 +481,106 @@ impl Request {
         JsonValue::Object(obj)
     }
 }
+
+/// Implements reading a request from a Json object, as it would be received by a DaZeus core.
+impl Request {
+    /// Create a request based on a Json object.
+    ///
+    /// This is the reverse of `Request::to_json()`. Note that some requests share the same Json
+    /// representation: a `Request::Subscribe` for a command is read as an equivalent
+    /// `Request::SubscribeCommand`, and a `Request::Handshake` that has the plugin name as its
+    /// config name is read as one without a config name.
+    ///
+    /// # Example
+    /// ```
+    /// # use dazeus::*;
+    /// let request = Request::Join("freenode".to_string(), "#freenode".to_string());
+    /// assert_eq!(Request::from_json(&request.to_json()), Ok(request));
+    /// ```
+    pub fn from_json(data: &JsonValue) -> Result<Request, InvalidJsonError> {
+        let obj = match data.as_object() {
+            Some(obj) => obj,
+            None => return Err(InvalidJsonError::new("Request is not an object")),
+        };
+
+        let name = match (obj.get("get"), obj.get("do")) {
+            (Some(JsonValue::String(name)), None) => name,
+            (None, Some(JsonValue::String(name))) => name,
+            _ => return Err(InvalidJsonError::new("Request has no valid action")),
+        };
+
+        let params = match obj.get("params") {
+            Some(JsonValue::Array(params)) => &params[..],
+            Some(_) => return Err(InvalidJsonError::new("Request parameters are not an array")),
+            None => &[],
+        };
+
+        let scope = match obj.get("scope") {
+            Some(scope) => Scope::from_json(scope)?,
+            None => Scope::any(),
+        };
+
+        let string = |idx: usize| -> Result<String, InvalidJsonError> {
+            match params.get(idx) {
+                Some(JsonValue::String(s)) => Ok(s.clone()),
+                _ => Err(InvalidJsonError::new("Missing string parameter")),
+            }
+        };
+
+        let boolean = |idx: usize| -> Result<bool, InvalidJsonError> {
+            match params.get(idx) {
+                Some(&JsonValue::Bool(b)) => Ok(b),
+                _ => Err(InvalidJsonError::new("Missing boolean parameter")),
+            }
+        };
+
+        let event = |idx: usize| -> Result<EventType, InvalidJsonError> {
+            match EventType::from_str(&string(idx)?) {
+                Ok(evt) => Ok(evt),
+                Err(_) => Err(InvalidJsonError::new("Unknown event type")),
+            }
+        };
+
+        match &name[..] {
+            "subscribe" => Ok(Request::Subscribe(event(0)?)),
+            "unsubscribe" => Ok(Request::Unsubscribe(event(0)?)),
+            "command" => Ok(Request::SubscribeCommand(string(0)?, string(1).ok())),
+            "networks" => Ok(Request::Networks),
+            "channels" => Ok(Request::Channels(string(0)?)),
+            "message" => Ok(Request::Message(string(0)?, string(1)?, string(2)?)),
+            "notice" => Ok(Request::Notice(string(0)?, string(1)?, string(2)?)),
+            "ctcp" => Ok(Request::Ctcp(string(0)?, string(1)?, string(2)?)),
+            "ctcp_rep" => Ok(Request::CtcpReply(string(0)?, string(1)?, string(2)?)),
+            "action" => Ok(Request::Action(string(0)?, string(1)?, string(2)?)),
+            "names" => Ok(Request::Names(string(0)?, string(1)?)),
+            "whois" => Ok(Request::Whois(string(0)?, string(1)?)),
+            "join" => Ok(Request::Join(string(0)?, string(1)?)),
+            "part" => Ok(Request::Part(string(0)?, string(1)?)),
+            "nick" => Ok(Request::Nick(string(0)?)),
+            "handshake" => {
+                let name = string(0)?;
+                let config = string(3)?;
+                let config = if config == name { None } else { Some(config) };
+                Ok(Request::Handshake(name, string(1)?, config))
+            }
+            "config" => match ConfigGroup::from_str(&string(0)?) {
+                Ok(group) => Ok(Request::Config(string(1)?, group)),
+                Err(_) => Err(InvalidJsonError::new("Unknown config group")),
+            },
+            "property" => match &string(0)?[..] {
+                "get" => Ok(Request::GetProperty(string(1)?, scope)),
+                "set" => Ok(Request::SetProperty(string(1)?, string(2)?, scope)),
+                "unset" => Ok(Request::UnsetProperty(string(1)?, scope)),
+                "keys" => Ok(Request::PropertyKeys(string(1)?, scope)),
+                _ => Err(InvalidJsonError::new("Unknown property action")),
+            },
+            "permission" => match &string(0)?[..] {
+                "set" => Ok(Request::SetPermission(string(1)?, boolean(2)?, scope)),
+                "get" => Ok(Request::HasPermission(string(1)?, boolean(2)?, scope)),
+                "unset" => Ok(Request::UnsetPermission(string(1)?, scope)),
+                _ => Err(InvalidJsonError::new("Unknown permission action")),
+            },
+            _ => Err(InvalidJsonError::new("Unknown request")),
+        }
+    }
+}
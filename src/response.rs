@@ -36,6 +36,20 @@ impl Response {
         }
     }
 
+    /// Create a new response for a request that was queued to be sent later.
+    ///
+    /// This is used when a message was held back by the rate limiter (see
+    /// `DaZeus::set_rate_limit()`). The response has the `queued` property set to true.
+    pub fn for_queued() -> Response {
+        let mut obj = Map::new();
+        obj.insert("success".to_string(), JsonValue::Bool(true));
+        obj.insert("queued".to_string(), JsonValue::Bool(true));
+
+        Response {
+            data: JsonValue::Object(obj),
+        }
+    }
+
     /// Create a new response based on a Json object.
     ///
     /// This is used by the bindings to create a new Response based on a json blob returned by the
@@ -44,6 +58,11 @@ impl Response {
         Ok(Response { data: data.clone() })
     }
 
+    /// Retrieve the Json object for this response, as it would be sent by the DaZeus core.
+    pub fn to_json(&self) -> JsonValue {
+        self.data.clone()
+    }
+
     /// Retrieve a property from the data object or return a default if it doesn't exist.
     pub fn get_or<'a>(&'a self, prop: &'a str, default: &'a JsonValue) -> &'a JsonValue {
         match self.get(prop) {
@@ -68,7 +87,7 @@ impl Response {
     /// property doesn't exist, or if it isn't of type `Json::String`.
     pub fn get_str<'a>(&'a self, prop: &'a str) -> Option<&'a str> {
         match self.get(prop) {
-            Some(&JsonValue::String(ref s)) => Some(&s[..]),
+            Some(JsonValue::String(s)) => Some(&s[..]),
             _ => None,
         }
     }
@@ -81,6 +100,32 @@ impl Response {
         }
     }
 
+    /// Retrieve a list of strings from the data object.
+    ///
+    /// Returns `Some(strings)` if the property exists and is an array, or `None` otherwise. Any
+    /// items in the array that are not strings are skipped.
+    pub fn get_str_list(&self, prop: &str) -> Option<Vec<String>> {
+        match self.get(prop) {
+            Some(JsonValue::Array(arr)) => Some(
+                arr.iter()
+                    .filter_map(|item| item.as_str().map(|s| s.to_string()))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Retrieve a boolean from the data object.
+    ///
+    /// Returns `Some(bool)` if the property exists and it was a boolean property, or `None`
+    /// otherwise.
+    pub fn get_bool(&self, prop: &str) -> Option<bool> {
+        match self.get(prop) {
+            Some(&JsonValue::Bool(b)) => Some(b),
+            _ => None,
+        }
+    }
+
     /// Returns whether or not a property with the given name exists.
     pub fn has(&self, prop: &str) -> bool {
         self.get_str(prop).is_some()
@@ -88,9 +133,6 @@ impl Response {
 
     /// Check whether a Response contains a `success` property and whether it was true.
     pub fn has_success(&self) -> bool {
-        match self.get("success") {
-            Some(&JsonValue::Bool(true)) => true,
-            _ => false,
-        }
+        matches!(self.get("success"), Some(&JsonValue::Bool(true)))
     }
 }
@@ -0,0 +1,172 @@
+use super::dazeus::DaZeusClient;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// A handle for cancelling a scheduled task.
+///
+/// Handles can be cloned, so a task can also be cancelled from within its own callback.
+#[derive(Debug, Clone)]
+pub struct TaskHandle {
+    cancelled: Rc<Cell<bool>>,
+}
+
+impl TaskHandle {
+    /// Cancel the task, so that it will not be run (again).
+    pub fn cancel(&self) {
+        self.cancelled.set(true);
+    }
+
+    /// Check whether the task was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+}
+
+pub(crate) struct Task<'a> {
+    due: Instant,
+    every: Option<Duration>,
+    handle: TaskHandle,
+    #[allow(clippy::type_complexity)]
+    callback: Box<dyn FnMut(&dyn DaZeusClient) + 'a>,
+}
+
+impl<'a> Task<'a> {
+    pub fn call(&mut self, dazeus: &dyn DaZeusClient) {
+        if !self.handle.is_cancelled() {
+            (self.callback)(dazeus);
+        }
+    }
+}
+
+/// Keeps track of the tasks that are waiting to be run.
+pub(crate) struct Scheduler<'a> {
+    tasks: Vec<Task<'a>>,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new() -> Scheduler<'a> {
+        Scheduler { tasks: Vec::new() }
+    }
+
+    /// Add a task that is first run at the given time, and then repeatedly (if `every` is set).
+    pub fn add<F>(&mut self, due: Instant, every: Option<Duration>, callback: F) -> TaskHandle
+    where
+        F: FnMut(&dyn DaZeusClient) + 'a,
+    {
+        let handle = TaskHandle {
+            cancelled: Rc::new(Cell::new(false)),
+        };
+        self.tasks.push(Task {
+            due,
+            every,
+            handle: handle.clone(),
+            callback: Box::new(callback),
+        });
+        handle
+    }
+
+    /// Take all tasks that should have run by now, in the order in which they are due.
+    pub fn take_due(&mut self) -> Vec<Task<'a>> {
+        let now = Instant::now();
+        self.tasks.retain(|task| !task.handle.is_cancelled());
+
+        let mut due = Vec::new();
+        let mut i = 0;
+        while i < self.tasks.len() {
+            if self.tasks[i].due <= now {
+                due.push(self.tasks.swap_remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        due.sort_by_key(|task| task.due);
+        due
+    }
+
+    /// Put a task that was run back into the schedule if it should be repeated.
+    pub fn reschedule(&mut self, mut task: Task<'a>) {
+        if task.handle.is_cancelled() {
+            return;
+        }
+
+        if let Some(every) = task.every {
+            // don't try to catch up on runs that were missed
+            let now = Instant::now();
+            task.due = if task.due + every > now {
+                task.due + every
+            } else {
+                now + every
+            };
+            self.tasks.push(task);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dazeus::DaZeus;
+    use crate::testing::MockCore;
+    use std::cell::RefCell;
+
+    #[test]
+    fn due_tasks_run_in_order() {
+        let (_core, conn) = MockCore::new();
+        let dazeus = DaZeus::new(conn);
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let now = Instant::now();
+        let mut scheduler = Scheduler::new();
+        for &(name, due) in &[
+            ("late", now - Duration::from_secs(1)),
+            ("future", now + Duration::from_secs(60)),
+            ("early", now - Duration::from_secs(2)),
+        ] {
+            let runs = runs.clone();
+            scheduler.add(due, None, move |_| runs.borrow_mut().push(name));
+        }
+
+        for mut task in scheduler.take_due() {
+            task.call(&dazeus);
+            scheduler.reschedule(task);
+        }
+        assert_eq!(*runs.borrow(), ["early", "late"]);
+        assert_eq!(scheduler.tasks.len(), 1);
+    }
+
+    #[test]
+    fn repeating_task_does_not_catch_up() {
+        let mut scheduler = Scheduler::new();
+        let every = Duration::from_secs(1);
+        scheduler.add(
+            Instant::now() - Duration::from_secs(10),
+            Some(every),
+            |_| (),
+        );
+
+        let task = scheduler.take_due().pop().unwrap();
+        let now = Instant::now();
+        scheduler.reschedule(task);
+        assert!(scheduler.take_due().is_empty());
+        assert!(scheduler.tasks[0].due >= now + every);
+    }
+
+    #[test]
+    fn cancel_from_own_callback() {
+        let (_core, conn) = MockCore::with_timeouts();
+        let dazeus = DaZeus::new(conn);
+        let runs = Rc::new(Cell::new(0));
+        let handle: Rc<RefCell<Option<TaskHandle>>> = Rc::new(RefCell::new(None));
+
+        let (counter, own) = (runs.clone(), handle.clone());
+        *handle.borrow_mut() = Some(dazeus.schedule_every(Duration::from_millis(1), move |_| {
+            counter.set(counter.get() + 1);
+            own.borrow().as_ref().unwrap().cancel();
+        }));
+        dazeus.schedule_in(Duration::from_millis(50), |dazeus| dazeus.stop());
+
+        dazeus.listen().unwrap();
+        assert_eq!(runs.get(), 1);
+        assert!(handle.borrow().as_ref().unwrap().is_cancelled());
+    }
+}
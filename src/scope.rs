@@ -1,3 +1,6 @@
+use super::error::{InvalidEventError, InvalidJsonError};
+use super::event::Event;
+use super::typed::TypedEvent;
 use serde_json::Value as JsonValue;
 
 /// A scope for retrieving permissions and properties.
@@ -47,17 +50,17 @@ impl Scope {
         Scope::new(Some(network.to_string()), None, None)
     }
 
-    /// Scope to a specific sender (typically a channel)
+    /// Scope to a specific sender (typically a user)
     pub fn sender(network: &str, sender: &str) -> Scope {
         Scope::new(Some(network.to_string()), Some(sender.to_string()), None)
     }
 
-    /// Scope to a specific receiver (typically a user)
+    /// Scope to a specific receiver (typically a channel)
     pub fn receiver(network: &str, receiver: &str) -> Scope {
         Scope::new(Some(network.to_string()), None, Some(receiver.to_string()))
     }
 
-    /// Scope to a specific receiver and channel (typically a user in a channel)
+    /// Scope to a specific sender and receiver (typically a user in a channel)
     pub fn to(network: &str, sender: &str, receiver: &str) -> Scope {
         Scope::new(
             Some(network.to_string()),
@@ -66,9 +69,110 @@ impl Scope {
         )
     }
 
+    /// Start building a scope.
+    ///
+    /// # Example
+    /// ```
+    /// # use dazeus::Scope;
+    /// let scope = Scope::builder().network("freenode").receiver("#dazeus").build();
+    /// assert_eq!(scope, Scope::new(Some("freenode".to_string()), None, Some("#dazeus".to_string())));
+    /// ```
+    pub fn builder() -> ScopeBuilder {
+        ScopeBuilder {
+            scope: Scope::any(),
+        }
+    }
+
+    /// Create the most specific scope that applies to some event.
+    ///
+    /// The network of the event is always used. The user that caused the event is used as the
+    /// sender and the channel it happened in is used as the receiver, if the event has them.
+    /// For example, a PRIVMSG in a channel results in a scope for the user in that channel,
+    /// while a QUIT results in a scope for the user on the network.
+    ///
+    /// Returns an error if the event does not contain the parameters required for its type.
+    ///
+    /// # Example
+    /// ```
+    /// # use dazeus::*;
+    /// let event = Event::new(EventType::PrivMsg, vec!(
+    ///    "freenode".to_string(),
+    ///    "MrExample".to_string(),
+    ///    "#dazeus".to_string(),
+    ///    "Hello!".to_string()
+    /// ));
+    /// let scope = Scope::from_event(&event).unwrap();
+    /// assert_eq!(scope.sender, Some("MrExample".to_string()));
+    /// assert_eq!(scope.receiver, Some("#dazeus".to_string()));
+    /// ```
+    pub fn from_event(event: &Event) -> Result<Scope, InvalidEventError> {
+        let (network, sender, receiver) = match event.as_typed()? {
+            TypedEvent::Action(e)
+            | TypedEvent::ActionMe(e)
+            | TypedEvent::Ctcp(e)
+            | TypedEvent::CtcpMe(e)
+            | TypedEvent::CtcpReply(e)
+            | TypedEvent::Notice(e)
+            | TypedEvent::PrivMsg(e)
+            | TypedEvent::PrivMsgMe(e) => (e.network, Some(e.sender), Some(e.channel)),
+            TypedEvent::Command(e) => (e.network, Some(e.sender), Some(e.channel)),
+            TypedEvent::Connect(e) | TypedEvent::Disconnect(e) => (e.network, None, None),
+            TypedEvent::Invite(e) => (e.network, Some(e.sender), Some(e.channel)),
+            TypedEvent::Join(e) => (e.network, Some(e.nick), Some(e.channel)),
+            TypedEvent::Kick(e) => (e.network, Some(e.kicker), Some(e.channel)),
+            TypedEvent::Mode(e) => (e.network, Some(e.sender), Some(e.target)),
+            TypedEvent::Names(e) => (e.network, None, Some(e.channel)),
+            TypedEvent::Nick(e) => (e.network, Some(e.old_nick), None),
+            TypedEvent::Numeric(e) => (e.network, None, None),
+            TypedEvent::Part(e) => (e.network, Some(e.nick), Some(e.channel)),
+            TypedEvent::Pong(e) => (e.network, None, None),
+            TypedEvent::Quit(e) => (e.network, Some(e.nick), None),
+            TypedEvent::Topic(e) => (e.network, Some(e.sender), Some(e.channel)),
+            TypedEvent::Whois(e) => (e.network, Some(e.nick), None),
+            TypedEvent::Unknown(e) => match e.params.first() {
+                Some(network) => (network.clone(), None, None),
+                None => return Ok(Scope::any()),
+            },
+        };
+        Ok(Scope::new(Some(network), sender, receiver))
+    }
+
+    /// Iterate over this scope and all less specific scopes that contain it.
+    ///
+    /// The sender is removed first, then the receiver, and then the network, ending with
+    /// `Scope::any()`. This is useful for looking up a setting for a user in a channel, falling
+    /// back to a setting for the channel, the network or a global setting.
+    ///
+    /// # Example
+    /// ```
+    /// # use dazeus::Scope;
+    /// let scopes = Scope::to("freenode", "MrExample", "#dazeus").widen().collect::<Vec<_>>();
+    /// assert_eq!(scopes, vec!(
+    ///     Scope::to("freenode", "MrExample", "#dazeus"),
+    ///     Scope::receiver("freenode", "#dazeus"),
+    ///     Scope::network("freenode"),
+    ///     Scope::any(),
+    /// ));
+    /// ```
+    pub fn widen(&self) -> impl Iterator<Item = Scope> {
+        let mut scopes = vec![self.clone()];
+        let mut scope = self.clone();
+        for step in 0..3 {
+            let changed = match step {
+                0 => scope.sender.take().is_some(),
+                1 => scope.receiver.take().is_some(),
+                _ => scope.network.take().is_some(),
+            };
+            if changed {
+                scopes.push(scope.clone());
+            }
+        }
+        scopes.into_iter()
+    }
+
     /// Checks whether the scope is set to be applied to everything.
     pub fn is_any(&self) -> bool {
-        self.network == None && self.sender == None && self.receiver == None
+        self.network.is_none() && self.sender.is_none() && self.receiver.is_none()
     }
 }
 
@@ -94,4 +198,64 @@ impl Scope {
         }
         JsonValue::Array(arr)
     }
+
+    /// Create a scope based on its Json representation, as created by `Scope::to_json()`.
+    pub fn from_json(data: &JsonValue) -> Result<Scope, InvalidJsonError> {
+        let arr = match data.as_array() {
+            Some(arr) if arr.len() <= 3 => arr,
+            _ => {
+                return Err(InvalidJsonError::new(
+                    "Scope is not an array of at most 3 items",
+                ))
+            }
+        };
+
+        let mut parts = Vec::new();
+        for item in arr {
+            parts.push(match *item {
+                JsonValue::Null => None,
+                JsonValue::String(ref s) => Some(s.clone()),
+                _ => return Err(InvalidJsonError::new("Scope item is not a string")),
+            });
+        }
+        parts.resize(3, None);
+
+        let receiver = parts.pop().unwrap();
+        let sender = parts.pop().unwrap();
+        let network = parts.pop().unwrap();
+        Ok(Scope::new(network, sender, receiver))
+    }
+}
+
+/// A builder for creating a `Scope` one limitation at a time.
+///
+/// Use `Scope::builder()` to start building a scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeBuilder {
+    scope: Scope,
+}
+
+impl ScopeBuilder {
+    /// Limit the scope to a network.
+    pub fn network(mut self, network: &str) -> ScopeBuilder {
+        self.scope.network = Some(network.to_string());
+        self
+    }
+
+    /// Limit the scope to a sender (typically a user).
+    pub fn sender(mut self, sender: &str) -> ScopeBuilder {
+        self.scope.sender = Some(sender.to_string());
+        self
+    }
+
+    /// Limit the scope to a receiver (typically a channel).
+    pub fn receiver(mut self, receiver: &str) -> ScopeBuilder {
+        self.scope.receiver = Some(receiver.to_string());
+        self
+    }
+
+    /// Create the scope.
+    pub fn build(self) -> Scope {
+        self.scope
+    }
 }
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A handle for stopping `DaZeus::listen()`.
+///
+/// Stop handles can be cloned and sent to other threads. Once `StopHandle::stop()` is called,
+/// `DaZeus::listen()` stops as soon as it is done handling the current event (or as soon as it
+/// stops waiting for the next one, see `Connection::set_read_timeout()`). Callbacks can also stop
+/// listening using `DaZeusClient::stop()`.
+///
+/// # Example
+/// ```no_run
+/// # use dazeus::*;
+/// # use std::str::FromStr;
+/// # use std::thread;
+/// # use std::time::Duration;
+/// let conn = Connection::from_str("unix:/tmp/dazeus.sock").unwrap();
+/// conn.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+/// let dazeus = DaZeus::new(conn);
+///
+/// let stop = dazeus.stop_handle();
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(60));
+///     stop.stop();
+/// });
+///
+/// dazeus.listen().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl StopHandle {
+    pub(crate) fn new() -> StopHandle {
+        StopHandle {
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Ask `DaZeus::listen()` to stop.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether stopping was requested and `DaZeus::listen()` has not stopped yet.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Allow listening again after `DaZeus::listen()` has stopped.
+    pub(crate) fn reset(&self) {
+        self.stopped.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(feature = "signals")]
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "signals")]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    SIGNALLED.store(true, Ordering::SeqCst);
+}
+
+/// Install handlers for SIGINT and SIGTERM that record that the signal was received.
+///
+/// The handlers are installed without `SA_RESTART`, so that a blocking read from the DaZeus core
+/// is interrupted when a signal arrives.
+#[cfg(feature = "signals")]
+pub(crate) fn catch_signals() -> std::io::Result<()> {
+    for signal in &[libc::SIGINT, libc::SIGTERM] {
+        // safe: the handler only stores to an atomic, which is async-signal-safe
+        let result = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = 0;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(*signal, &action, std::ptr::null_mut())
+        };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Check whether SIGINT or SIGTERM was received since the last time this was checked.
+#[cfg(feature = "signals")]
+pub(crate) fn take_signal() -> bool {
+    SIGNALLED.swap(false, Ordering::SeqCst)
+}
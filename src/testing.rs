@@ -0,0 +1,259 @@
+//! Support for testing plugins without a running DaZeus core.
+//!
+//! This module is only available when the `testing` feature is enabled. It provides a `MockCore`
+//! that implements the core side of the DaZeus protocol in memory. The `MockConnection` that is
+//! created along with it can be passed to `DaZeus::new` like any other connection.
+//!
+//! # Example
+//! ```
+//! # use dazeus::*;
+//! # use dazeus::testing::MockCore;
+//! let (core, conn) = MockCore::new();
+//! core.respond(Response::from_json(&serde_json::json!({"success": true, "nick": "DaZeus"})).unwrap());
+//!
+//! let dazeus = DaZeus::new(conn);
+//! assert_eq!(dazeus.nick("local"), Some("DaZeus".to_string()));
+//! assert_eq!(core.requests(), vec!(Request::Nick("local".to_string())));
+//! ```
+
+use super::event::{Event, EventType};
+use super::request::Request;
+use super::response::Response;
+use log::debug;
+use serde_json::Value as JsonValue;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::rc::Rc;
+use std::str::from_utf8;
+use std::thread;
+use std::time::Duration;
+
+/// How long a read from a `MockConnection` takes before it times out.
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+struct State {
+    incoming: Vec<u8>,
+    outgoing: VecDeque<u8>,
+    requests: Vec<Request>,
+    replies: VecDeque<(Response, Vec<Event>)>,
+    time_out_when_idle: bool,
+    timeouts: usize,
+}
+
+impl State {
+    /// Write a Json message to the plugin using the DaZeus framing.
+    fn send(&mut self, json: &serde_json::Value) {
+        let encoded = json.to_string();
+        debug!("Mock core sending message: {}", encoded);
+        self.outgoing
+            .extend(format!("{}", encoded.len()).as_bytes().iter());
+        self.outgoing.extend(encoded.as_bytes().iter());
+    }
+
+    /// Handle every complete request that has been written by the plugin.
+    fn process(&mut self) {
+        while let Some(json) = self.next_request() {
+            let request = match serde_json::from_str(&json) {
+                Ok(data) => Request::from_json(&data),
+                Err(_) => {
+                    self.send(&Response::for_fail("Invalid JSON").to_json());
+                    continue;
+                }
+            };
+
+            let (response, events) = match request {
+                Ok(request) => {
+                    self.requests.push(request);
+                    self.replies
+                        .pop_front()
+                        .unwrap_or_else(|| (Response::for_success(), Vec::new()))
+                }
+                Err(_) => (Response::for_fail("Invalid request"), Vec::new()),
+            };
+
+            self.send(&response.to_json());
+            for event in events {
+                self.send(&event.to_json());
+            }
+        }
+    }
+
+    /// Take the next complete request from the incoming buffer (if any).
+    fn next_request(&mut self) -> Option<String> {
+        let digits = self
+            .incoming
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+
+        // the length may still be followed by more digits
+        if digits == 0 || digits == self.incoming.len() {
+            return None;
+        }
+
+        let len = from_utf8(&self.incoming[..digits])
+            .ok()?
+            .parse::<usize>()
+            .ok()?;
+        if self.incoming.len() < digits + len {
+            return None;
+        }
+
+        let message = self.incoming[digits..digits + len].to_vec();
+        self.incoming.drain(..digits + len);
+        String::from_utf8(message).ok()
+    }
+}
+
+/// An in-memory DaZeus core for testing plugins.
+///
+/// Every request sent by the plugin is recorded and answered with the next scripted response,
+/// or with a plain successful response if nothing was scripted. Events can be sent to the plugin
+/// at any time using `MockCore::inject_event()`.
+///
+/// Once the plugin has read everything the mock core has sent, the connection behaves as if it
+/// was closed by the core. This means that `DaZeus::listen()` will return an error after all
+/// injected events have been handled. A mock core created using `MockCore::with_timeouts()`
+/// behaves like a connection with a read timeout instead.
+#[derive(Clone)]
+pub struct MockCore {
+    state: Rc<RefCell<State>>,
+}
+
+/// The plugin side of a connection to a `MockCore`.
+pub struct MockConnection {
+    state: Rc<RefCell<State>>,
+}
+
+impl MockCore {
+    /// Create a new mock core, along with a connection that can be used by `DaZeus`.
+    pub fn new() -> (MockCore, MockConnection) {
+        MockCore::create(false)
+    }
+
+    /// Create a new mock core with a connection that times out when there is nothing to read.
+    ///
+    /// Reads fail with `ErrorKind::WouldBlock` after a short while, like they do for a
+    /// `Connection` with a read timeout, so `DaZeus::listen()` keeps running until it is stopped.
+    pub fn with_timeouts() -> (MockCore, MockConnection) {
+        MockCore::create(true)
+    }
+
+    fn create(time_out_when_idle: bool) -> (MockCore, MockConnection) {
+        let state = Rc::new(RefCell::new(State {
+            incoming: Vec::new(),
+            outgoing: VecDeque::new(),
+            requests: Vec::new(),
+            replies: VecDeque::new(),
+            time_out_when_idle,
+            timeouts: 0,
+        }));
+        (
+            MockCore {
+                state: state.clone(),
+            },
+            MockConnection { state },
+        )
+    }
+
+    /// Let the next reads from the connection time out, even if the core has something to send.
+    ///
+    /// This can be used to test how a plugin handles a slow core. Use 0 to stop timing out.
+    pub fn time_out_reads(&self, count: usize) {
+        self.state.borrow_mut().timeouts = count;
+    }
+
+    /// Answer the next unanswered request with the given response.
+    ///
+    /// Responses are used in the order in which they were scripted.
+    pub fn respond(&self, response: Response) {
+        self.respond_with_events(response, Vec::new());
+    }
+
+    /// Answer the next unanswered request with the given response, followed by some events.
+    ///
+    /// This is useful for requests that are answered by events, such as `Request::Whois` and
+    /// `Request::Names`.
+    pub fn respond_with_events(&self, response: Response, events: Vec<Event>) {
+        self.state
+            .borrow_mut()
+            .replies
+            .push_back((response, events));
+    }
+
+    /// Send an event to the plugin.
+    pub fn inject_event(&self, event: Event) {
+        self.state.borrow_mut().send(&event.to_json());
+    }
+
+    /// Retrieve all requests that have been sent by the plugin so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.borrow().requests.clone()
+    }
+
+    /// Forget about all requests that have been sent by the plugin so far.
+    pub fn clear_requests(&self) {
+        self.state.borrow_mut().requests.clear();
+    }
+}
+
+impl Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.state.borrow_mut();
+        if state.timeouts > 0 || (state.time_out_when_idle && state.outgoing.is_empty()) {
+            state.timeouts = state.timeouts.saturating_sub(1);
+            thread::sleep(READ_TIMEOUT);
+            return Err(Error::new(
+                ErrorKind::WouldBlock,
+                "Mock core read timed out",
+            ));
+        }
+
+        let len = buf.len().min(state.outgoing.len());
+        for (target, byte) in buf.iter_mut().zip(state.outgoing.drain(..len)) {
+            *target = byte;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut state = self.state.borrow_mut();
+        state.incoming.extend_from_slice(buf);
+        state.process();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Create an event with the given parameters.
+pub fn event(event: EventType, params: &[&str]) -> Event {
+    Event::new(event, params.iter().map(|p| p.to_string()).collect())
+}
+
+/// Create a `PRIVMSG` event for a message from some sender to a channel (or to the bot).
+pub fn privmsg(network: &str, sender: &str, channel: &str, message: &str) -> Event {
+    event(EventType::PrivMsg, &[network, sender, channel, message])
+}
+
+/// Create a response from its Json representation.
+///
+/// Panics if the Json is not a valid response, which makes it easy to script responses in tests.
+///
+/// # Example
+/// ```
+/// # use dazeus::testing::response;
+/// let nick = response(serde_json::json!({"success": true, "nick": "DaZeus"}));
+/// assert_eq!(nick.get_str("nick"), Some("DaZeus"));
+/// ```
+pub fn response(data: JsonValue) -> Response {
+    match Response::from_json(&data) {
+        Ok(response) => response,
+        Err(e) => panic!("Invalid response {}: {:?}", data, e),
+    }
+}
@@ -0,0 +1,444 @@
+use super::error::InvalidEventError;
+use super::event::{Event, EventType};
+use std::convert::TryFrom;
+
+/// A network was connected to or disconnected from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectEvent {
+    /// The network the bot connected to or disconnected from.
+    pub network: String,
+}
+
+/// A message sent to a channel or user, such as a PRIVMSG, NOTICE, CTCP or CTCP ACTION.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageEvent {
+    /// The network on which the message was sent.
+    pub network: String,
+    /// The nick of the user that sent the message.
+    pub sender: String,
+    /// The channel the message was sent to, or the nick of the bot for private messages.
+    pub channel: String,
+    /// The contents of the message.
+    pub message: String,
+}
+
+/// A command sent to the bot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandEvent {
+    /// The network on which the command was given.
+    pub network: String,
+    /// The nick of the user that gave the command.
+    pub sender: String,
+    /// The channel the command was given in, or the nick of the bot for private messages.
+    pub channel: String,
+    /// The name of the command.
+    pub command: String,
+    /// Everything following the command name, as it was typed by the user.
+    pub rest: String,
+}
+
+/// A user joined a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinEvent {
+    /// The network on which the channel was joined.
+    pub network: String,
+    /// The nick of the user that joined the channel.
+    pub nick: String,
+    /// The channel that was joined.
+    pub channel: String,
+}
+
+/// A user left a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartEvent {
+    /// The network on which the channel was left.
+    pub network: String,
+    /// The nick of the user that left the channel.
+    pub nick: String,
+    /// The channel that was left.
+    pub channel: String,
+    /// The part message (empty if none was given).
+    pub message: String,
+}
+
+/// A user was kicked from a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KickEvent {
+    /// The network on which the kick took place.
+    pub network: String,
+    /// The nick of the user that kicked the victim.
+    pub kicker: String,
+    /// The channel the victim was kicked from.
+    pub channel: String,
+    /// The nick of the user that was kicked.
+    pub victim: String,
+    /// The reason given for the kick (empty if none was given).
+    pub reason: String,
+}
+
+/// A user disconnected from the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuitEvent {
+    /// The network the user disconnected from.
+    pub network: String,
+    /// The nick of the user that disconnected.
+    pub nick: String,
+    /// The quit message (empty if none was given).
+    pub message: String,
+}
+
+/// A user changed nicks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NickEvent {
+    /// The network on which the nick was changed.
+    pub network: String,
+    /// The nick of the user before the change.
+    pub old_nick: String,
+    /// The nick of the user after the change.
+    pub new_nick: String,
+}
+
+/// The bot was invited to a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InviteEvent {
+    /// The network on which the invite was sent.
+    pub network: String,
+    /// The nick of the user that sent the invite.
+    pub sender: String,
+    /// The channel the bot was invited to.
+    pub channel: String,
+}
+
+/// The topic of a channel was received or changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicEvent {
+    /// The network the channel is on.
+    pub network: String,
+    /// The nick of the user (or the server) that set the topic.
+    pub sender: String,
+    /// The channel of which the topic was set.
+    pub channel: String,
+    /// The new topic.
+    pub topic: String,
+}
+
+/// A mode was changed on a channel or user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeEvent {
+    /// The network on which the mode was changed.
+    pub network: String,
+    /// The nick of the user that changed the mode.
+    pub sender: String,
+    /// The channel or nick of which the mode was changed.
+    pub target: String,
+    /// The mode change itself, such as `+o`.
+    pub mode: String,
+    /// Any arguments to the mode change.
+    pub args: Vec<String>,
+}
+
+/// The list of nicks in a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamesEvent {
+    /// The network the channel is on.
+    pub network: String,
+    /// The server that sent the names list.
+    pub server: String,
+    /// The channel the names list is for.
+    pub channel: String,
+    /// The nicks in the channel.
+    pub nicks: Vec<String>,
+}
+
+/// The response to a whois request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhoisEvent {
+    /// The network on which the whois was requested.
+    pub network: String,
+    /// The server that answered the whois request.
+    pub server: String,
+    /// The nick that the whois request was for.
+    pub nick: String,
+    /// Whether the nick is identified with services.
+    pub identified: bool,
+}
+
+/// A numeric reply sent by the IRC server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericEvent {
+    /// The network that sent the reply.
+    pub network: String,
+    /// The server that sent the reply.
+    pub server: String,
+    /// The numeric code of the reply.
+    pub code: u16,
+    /// The parameters of the reply.
+    pub params: Vec<String>,
+}
+
+/// The response to a ping.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PongEvent {
+    /// The network that sent the pong.
+    pub network: String,
+    /// The server that sent the pong.
+    pub server: String,
+    /// The message sent along with the pong.
+    pub message: String,
+}
+
+/// A typed view on an `Event`, with named fields instead of positional parameters.
+///
+/// Use `Event::as_typed()` to create a typed event from a received event. Alternatively, any of
+/// the structs contained in the variants can be created directly using `TryFrom<Event>`.
+///
+/// # Example
+/// ```
+/// # use dazeus::*;
+/// let event = Event::new(EventType::PrivMsg, vec!(
+///    "network".to_string(),
+///    "sender".to_string(),
+///    "#channel".to_string(),
+///    "message".to_string()
+/// ));
+/// match event.as_typed() {
+///     Ok(TypedEvent::PrivMsg(msg)) => assert_eq!(msg.channel, "#channel"),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedEvent {
+    /// See `EventType::Action`.
+    Action(MessageEvent),
+    /// See `EventType::ActionMe`.
+    ActionMe(MessageEvent),
+    /// See `EventType::Command`.
+    Command(CommandEvent),
+    /// See `EventType::Connect`.
+    Connect(ConnectEvent),
+    /// See `EventType::Ctcp`.
+    Ctcp(MessageEvent),
+    /// See `EventType::CtcpMe`.
+    CtcpMe(MessageEvent),
+    /// See `EventType::CtcpReply`.
+    CtcpReply(MessageEvent),
+    /// See `EventType::Disconnect`.
+    Disconnect(ConnectEvent),
+    /// See `EventType::Invite`.
+    Invite(InviteEvent),
+    /// See `EventType::Join`.
+    Join(JoinEvent),
+    /// See `EventType::Kick`.
+    Kick(KickEvent),
+    /// See `EventType::Mode`.
+    Mode(ModeEvent),
+    /// See `EventType::Names`.
+    Names(NamesEvent),
+    /// See `EventType::Nick`.
+    Nick(NickEvent),
+    /// See `EventType::Notice`.
+    Notice(MessageEvent),
+    /// See `EventType::Numeric`.
+    Numeric(NumericEvent),
+    /// See `EventType::Part`.
+    Part(PartEvent),
+    /// See `EventType::Pong`.
+    Pong(PongEvent),
+    /// See `EventType::PrivMsg`.
+    PrivMsg(MessageEvent),
+    /// See `EventType::PrivMsgMe`.
+    PrivMsgMe(MessageEvent),
+    /// See `EventType::Quit`.
+    Quit(QuitEvent),
+    /// See `EventType::Topic`.
+    Topic(TopicEvent),
+    /// See `EventType::Unknown`, the original event is kept as is.
+    Unknown(Event),
+    /// See `EventType::Whois`.
+    Whois(WhoisEvent),
+}
+
+impl Event {
+    /// Create a typed view on this event.
+    ///
+    /// Returns an error if the event does not contain the parameters required for its type.
+    pub fn as_typed(&self) -> Result<TypedEvent, InvalidEventError> {
+        let p = Params(self);
+        Ok(match self.event {
+            EventType::Action => TypedEvent::Action(p.message()?),
+            EventType::ActionMe => TypedEvent::ActionMe(p.message()?),
+            EventType::Command(ref command) => TypedEvent::Command(CommandEvent {
+                network: p.required(0)?,
+                sender: p.required(1)?,
+                channel: p.required(2)?,
+                command: command.clone(),
+                rest: p.optional(4),
+            }),
+            EventType::Connect => TypedEvent::Connect(p.connect()?),
+            EventType::Ctcp => TypedEvent::Ctcp(p.message()?),
+            EventType::CtcpMe => TypedEvent::CtcpMe(p.message()?),
+            EventType::CtcpReply => TypedEvent::CtcpReply(p.message()?),
+            EventType::Disconnect => TypedEvent::Disconnect(p.connect()?),
+            EventType::Invite => TypedEvent::Invite(InviteEvent {
+                network: p.required(0)?,
+                sender: p.required(1)?,
+                channel: p.required(2)?,
+            }),
+            EventType::Join => TypedEvent::Join(JoinEvent {
+                network: p.required(0)?,
+                nick: p.required(1)?,
+                channel: p.required(2)?,
+            }),
+            EventType::Kick => TypedEvent::Kick(KickEvent {
+                network: p.required(0)?,
+                kicker: p.required(1)?,
+                channel: p.required(2)?,
+                victim: p.required(3)?,
+                reason: p.optional(4),
+            }),
+            EventType::Mode => TypedEvent::Mode(ModeEvent {
+                network: p.required(0)?,
+                sender: p.required(1)?,
+                target: p.required(2)?,
+                mode: p.required(3)?,
+                args: p.rest(4),
+            }),
+            EventType::Names => TypedEvent::Names(NamesEvent {
+                network: p.required(0)?,
+                server: p.required(1)?,
+                channel: p.required(2)?,
+                nicks: p.rest(3),
+            }),
+            EventType::Nick => TypedEvent::Nick(NickEvent {
+                network: p.required(0)?,
+                old_nick: p.required(1)?,
+                new_nick: p.required(2)?,
+            }),
+            EventType::Notice => TypedEvent::Notice(p.message()?),
+            EventType::Numeric => TypedEvent::Numeric(NumericEvent {
+                network: p.required(0)?,
+                server: p.required(1)?,
+                code: match p.required(2)?.parse() {
+                    Ok(code) => code,
+                    Err(_) => return Err(InvalidEventError::new("Invalid numeric code")),
+                },
+                params: p.rest(3),
+            }),
+            EventType::Part => TypedEvent::Part(PartEvent {
+                network: p.required(0)?,
+                nick: p.required(1)?,
+                channel: p.required(2)?,
+                message: p.optional(3),
+            }),
+            EventType::Pong => TypedEvent::Pong(PongEvent {
+                network: p.required(0)?,
+                server: p.required(1)?,
+                message: p.optional(2),
+            }),
+            EventType::PrivMsg => TypedEvent::PrivMsg(p.message()?),
+            EventType::PrivMsgMe => TypedEvent::PrivMsgMe(p.message()?),
+            EventType::Quit => TypedEvent::Quit(QuitEvent {
+                network: p.required(0)?,
+                nick: p.required(1)?,
+                message: p.optional(2),
+            }),
+            EventType::Topic => TypedEvent::Topic(TopicEvent {
+                network: p.required(0)?,
+                sender: p.required(1)?,
+                channel: p.required(2)?,
+                topic: p.optional(3),
+            }),
+            EventType::Unknown => TypedEvent::Unknown(self.clone()),
+            EventType::Whois => TypedEvent::Whois(WhoisEvent {
+                network: p.required(0)?,
+                server: p.required(1)?,
+                nick: p.required(2)?,
+                identified: p.optional(3) == "true",
+            }),
+        })
+    }
+}
+
+/// Helper for extracting parameters from an event.
+struct Params<'e>(&'e Event);
+
+impl<'e> Params<'e> {
+    fn required(&self, idx: usize) -> Result<String, InvalidEventError> {
+        match self.0.params.get(idx) {
+            Some(param) => Ok(param.clone()),
+            None => Err(InvalidEventError::new(&format!(
+                "Missing parameter {} for {} event",
+                idx, self.0.event
+            ))),
+        }
+    }
+
+    fn optional(&self, idx: usize) -> String {
+        self.0.params.get(idx).cloned().unwrap_or_default()
+    }
+
+    fn rest(&self, idx: usize) -> Vec<String> {
+        self.0.params.iter().skip(idx).cloned().collect()
+    }
+
+    fn connect(&self) -> Result<ConnectEvent, InvalidEventError> {
+        Ok(ConnectEvent {
+            network: self.required(0)?,
+        })
+    }
+
+    fn message(&self) -> Result<MessageEvent, InvalidEventError> {
+        Ok(MessageEvent {
+            network: self.required(0)?,
+            sender: self.required(1)?,
+            channel: self.required(2)?,
+            message: self.required(3)?,
+        })
+    }
+}
+
+impl TryFrom<Event> for TypedEvent {
+    type Error = InvalidEventError;
+
+    fn try_from(event: Event) -> Result<TypedEvent, InvalidEventError> {
+        event.as_typed()
+    }
+}
+
+macro_rules! try_from_event {
+    ($name: ident, $($variant: ident)|+) => {
+        impl TryFrom<Event> for $name {
+            type Error = InvalidEventError;
+
+            fn try_from(event: Event) -> Result<$name, InvalidEventError> {
+                match event.as_typed()? {
+                    $(TypedEvent::$variant(e))|+ => Ok(e),
+                    _ => Err(InvalidEventError::new(&format!(
+                        "Cannot create a {} from a {} event",
+                        stringify!($name),
+                        event.event
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+try_from_event!(ConnectEvent, Connect | Disconnect);
+try_from_event!(
+    MessageEvent,
+    Action | ActionMe | Ctcp | CtcpMe | CtcpReply | Notice | PrivMsg | PrivMsgMe
+);
+try_from_event!(CommandEvent, Command);
+try_from_event!(JoinEvent, Join);
+try_from_event!(PartEvent, Part);
+try_from_event!(KickEvent, Kick);
+try_from_event!(QuitEvent, Quit);
+try_from_event!(NickEvent, Nick);
+try_from_event!(InviteEvent, Invite);
+try_from_event!(TopicEvent, Topic);
+try_from_event!(ModeEvent, Mode);
+try_from_event!(NamesEvent, Names);
+try_from_event!(WhoisEvent, Whois);
+try_from_event!(NumericEvent, Numeric);
+try_from_event!(PongEvent, Pong);